@@ -1,6 +1,93 @@
 use raytracing::App;
 
+struct Args {
+    scene_path: Option<std::path::PathBuf>,
+    render: Option<std::path::PathBuf>,
+    out: std::path::PathBuf,
+    depth: Option<std::path::PathBuf>,
+    normals: Option<std::path::PathBuf>,
+    width: usize,
+    height: usize,
+}
+
+fn parse_args() -> Result<Args, String> {
+    let mut args = Args {
+        scene_path: None,
+        render: None,
+        out: "render.png".into(),
+        depth: None,
+        normals: None,
+        width: 1280,
+        height: 720,
+    };
+
+    let mut iter = std::env::args_os().skip(1);
+    while let Some(arg) = iter.next() {
+        let mut value = |name: &str| {
+            iter.next()
+                .ok_or_else(|| format!("expected a value after {name}"))
+        };
+        let parse_size = |name: &str, value: std::ffi::OsString| {
+            value
+                .to_str()
+                .and_then(|value| value.parse().ok())
+                .ok_or_else(|| format!("{name} expects a positive integer"))
+        };
+        match arg.to_str() {
+            Some("--render") => args.render = Some(value("--render")?.into()),
+            Some("--out") => args.out = value("--out")?.into(),
+            Some("--depth") => args.depth = Some(value("--depth")?.into()),
+            Some("--normals") => args.normals = Some(value("--normals")?.into()),
+            Some("--width") => args.width = parse_size("--width", value("--width")?)?,
+            Some("--height") => args.height = parse_size("--height", value("--height")?)?,
+            Some(flag) if flag.starts_with("--") => return Err(format!("unknown flag {flag}")),
+            _ => args.scene_path = Some(arg.into()),
+        }
+    }
+
+    Ok(args)
+}
+
 fn main() -> Result<(), eframe::Error> {
+    let args = match parse_args() {
+        Ok(args) => args,
+        Err(error) => {
+            eprintln!("{error}");
+            eprintln!("usage: raytracing [scene.json] [--render scene.json --out image.png [--depth depth.png|depth.exr] [--normals normals.png|normals.exr] --width W --height H]");
+            eprintln!("the RAYTRACE_SCENE environment variable overrides scene.json");
+            std::process::exit(2);
+        }
+    };
+
+    if let Some(scene_path) = &args.render {
+        if let Err(error) = raytracing::render_to_file(
+            scene_path,
+            &args.out,
+            args.depth.as_deref(),
+            args.normals.as_deref(),
+            (args.width, args.height),
+        ) {
+            eprintln!("{error}");
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
+
+    // the environment variable wins so a dev shell can pin a scene regardless of how it's launched
+    let env_scene_path = std::env::var_os("RAYTRACE_SCENE")
+        .map(std::path::PathBuf::from)
+        .filter(|path| {
+            let exists = path.is_file();
+            if !exists {
+                eprintln!(
+                    "warning: RAYTRACE_SCENE '{}' is not a file, ignoring it",
+                    path.display()
+                );
+            }
+            exists
+        });
+    let scene_path = env_scene_path.or(args.scene_path);
+
     let native_options = eframe::NativeOptions {
         renderer: eframe::Renderer::Wgpu,
         vsync: false,
@@ -9,6 +96,6 @@ fn main() -> Result<(), eframe::Error> {
     eframe::run_native(
         "Ray tracing",
         native_options,
-        Box::new(|cc| Box::new(App::new(cc))),
+        Box::new(move |cc| Box::new(App::new(cc, scene_path.as_deref()))),
     )
 }
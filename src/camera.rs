@@ -0,0 +1,355 @@
+use crate::shader_types::CameraUniform;
+use cgmath::{InnerSpace, Matrix4, Quaternion, Rotation3, VectorSpace};
+
+#[derive(Clone, Copy, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct Camera {
+    pub position: cgmath::Vector3<f32>,
+    pub rotation: Quaternion<f32>,
+    pub up_sky_color: cgmath::Vector3<f32>,
+    pub down_sky_color: cgmath::Vector3<f32>,
+    // scales both sky colors, so the sky can be brightened or dimmed without re-picking them
+    pub sky_intensity: f32,
+    pub min_distance: f32,
+    pub max_distance: f32,
+    pub shadow_bias: f32,
+    pub eye_separation: f32,
+    pub vignette: bool,
+    // how far from the center the darkening starts, 1 is the corners
+    pub vignette_radius: f32,
+    pub vignette_intensity: f32,
+    // 0 disables the fog
+    pub fog_density: f32,
+    pub fog_color: cgmath::Vector3<f32>,
+    pub fog_sky: bool,
+    // multiplies the final color, auto exposure adjusts this over time
+    pub exposure: f32,
+    // the width of a pixel over its height, anything but 1 squeezes the image horizontally
+    pub pixel_aspect: f32,
+}
+
+impl Camera {
+    /// Whether the sky colors times the intensity stay finite, an overflow would put
+    /// infinities into the lighting
+    pub fn sky_is_finite(&self) -> bool {
+        [self.up_sky_color, self.down_sky_color]
+            .into_iter()
+            .map(|color| color * self.sky_intensity)
+            .all(|color| color.x.is_finite() && color.y.is_finite() && color.z.is_finite())
+    }
+}
+
+impl Default for Camera {
+    fn default() -> Self {
+        Self {
+            position: (0.0, 0.0, -3.0).into(),
+            rotation: Quaternion::from_axis_angle((0.0, 0.0, 1.0).into(), cgmath::Deg(0.0)),
+            up_sky_color: (1.0, 1.0, 1.0).into(),
+            down_sky_color: (0.5, 0.7, 1.0).into(),
+            sky_intensity: 1.0,
+            min_distance: 0.001,
+            max_distance: 1000.0,
+            shadow_bias: 0.002,
+            eye_separation: 0.064,
+            vignette: false,
+            vignette_radius: 0.5,
+            vignette_intensity: 0.5,
+            fog_density: 0.0,
+            fog_color: (0.7, 0.75, 0.8).into(),
+            fog_sky: false,
+            exposure: 1.0,
+            pixel_aspect: 1.0,
+        }
+    }
+}
+
+impl Camera {
+    // columns are the same right/up/forward basis that `CameraUniform` uses
+    pub fn world_matrix(&self) -> Matrix4<f32> {
+        Matrix4::from_translation(self.position) * Matrix4::from(self.rotation)
+    }
+
+    // moves the camera back along its view direction until the bounding sphere of the box fits the view,
+    // `aspect` is the width of the view over its height
+    pub fn frame_bounds(
+        &mut self,
+        (min, max): (cgmath::Vector3<f32>, cgmath::Vector3<f32>),
+        aspect: f32,
+    ) {
+        let center = (min + max) * 0.5;
+        let radius = (max - min).magnitude() * 0.5;
+        // the vertical field of view is 90 degrees, the horizontal one is narrower in a tall view
+        let half_fov = (aspect * self.pixel_aspect).min(1.0).atan();
+        let distance = radius / half_fov.sin();
+        let forward = self.rotation * cgmath::vec3(0.0, 0.0, 1.0);
+        self.position = center - forward * distance;
+    }
+
+    /// Leaves the camera alone if the matrix has non-finite values, or if its forward and right
+    /// columns don't span a plane to build a rotation from
+    pub fn set_world_matrix(&mut self, matrix: Matrix4<f32>) -> Result<(), String> {
+        let columns: [[f32; 4]; 4] = matrix.into();
+        if !columns.iter().flatten().all(|value| value.is_finite()) {
+            return Err("the matrix has values that aren't finite".into());
+        }
+
+        // scaled or sheared columns aren't a rotation, gram-schmidt them into one that keeps the
+        // view direction, the same way `CameraUniform` builds its basis
+        let forward = direction(matrix.z.truncate()).ok_or("the third (forward) column is zero")?;
+        let right = direction(matrix.x.truncate()).ok_or("the first (right) column is zero")?;
+        let right = right - forward * forward.dot(right);
+        if right.magnitude2() < 1e-8 {
+            return Err("the first (right) column points along the forward column".into());
+        }
+        let right = right.normalize();
+        let up = forward.cross(right);
+        let rotation = Quaternion::from(cgmath::Matrix3::from_cols(right, up, forward)).normalize();
+
+        self.position = matrix.w.truncate();
+        self.rotation = rotation;
+        Ok(())
+    }
+}
+
+// scaling by the largest component first keeps huge values from overflowing while normalizing
+fn direction(vector: cgmath::Vector3<f32>) -> Option<cgmath::Vector3<f32>> {
+    let largest = vector.x.abs().max(vector.y.abs()).max(vector.z.abs());
+    (largest > 0.0).then(|| (vector / largest).normalize())
+}
+
+/// Places the camera on a sphere around `target`, looking at it
+#[derive(Clone, Copy)]
+pub struct Orbit {
+    pub target: cgmath::Vector3<f32>,
+    pub radius: f32,
+    // a full rotation rather than angles, so arcball dragging can go over the poles
+    pub rotation: Quaternion<f32>,
+}
+
+impl Orbit {
+    // the target is put `radius` in front of the camera, so applying this straight away doesnt move it
+    pub fn from_camera(camera: &Camera, radius: f32) -> Self {
+        let rotation = camera.rotation.normalize();
+        Self {
+            target: camera.position + rotation * cgmath::vec3(0.0, 0.0, radius),
+            radius,
+            rotation,
+        }
+    }
+
+    pub fn apply(&self, camera: &mut Camera) {
+        camera.rotation = self.rotation;
+        let forward = self.rotation * cgmath::vec3(0.0, 0.0, 1.0);
+        camera.position = self.target - forward * self.radius;
+    }
+
+    /// The angle around the y axis and the angle above the horizon the camera looks from
+    pub fn angles(&self) -> (cgmath::Deg<f32>, cgmath::Deg<f32>) {
+        let forward = self.rotation * cgmath::vec3(0.0, 0.0, 1.0);
+        (
+            cgmath::Rad(forward.x.atan2(forward.z)).into(),
+            cgmath::Rad((-forward.y).clamp(-1.0, 1.0).asin()).into(),
+        )
+    }
+
+    // any roll is lost
+    pub fn set_angles(&mut self, azimuth: cgmath::Deg<f32>, elevation: cgmath::Deg<f32>) {
+        self.rotation = Quaternion::from_angle_y(azimuth) * Quaternion::from_angle_x(elevation);
+    }
+
+    /// Rotates as if the cursor dragged a ball around the target from `from` to `to`, both are
+    /// in -1..1 across the viewport with y up
+    pub fn arcball(&mut self, from: cgmath::Vector2<f32>, to: cgmath::Vector2<f32>) {
+        // points outside the ball get pulled onto its edge, -z faces the camera
+        let project = |point: cgmath::Vector2<f32>| {
+            let length2 = point.magnitude2();
+            if length2 < 1.0 {
+                cgmath::vec3(point.x, point.y, -(1.0 - length2).sqrt())
+            } else {
+                point.normalize().extend(0.0)
+            }
+        };
+        let rotation = Quaternion::from_arc(project(from), project(to), None);
+        // the camera goes the opposite way to how the ball turns
+        self.rotation = (self.rotation * rotation.conjugate()).normalize();
+    }
+}
+
+/// Smoothly moves the camera's position and rotation between two cameras, nothing else is changed
+pub struct CameraTween {
+    from: Camera,
+    to: Camera,
+    elapsed: f32,
+    duration: f32,
+}
+
+impl CameraTween {
+    pub fn new(from: Camera, to: Camera, duration: f32) -> Self {
+        Self {
+            from,
+            to,
+            elapsed: 0.0,
+            duration,
+        }
+    }
+
+    /// Advances the tween by `ts` seconds and moves `camera`, returns whether it has finished
+    pub fn step(&mut self, ts: f32, camera: &mut Camera) -> bool {
+        self.elapsed += ts;
+        let t = (self.elapsed / self.duration.max(f32::EPSILON)).min(1.0);
+        // smoothstep so it eases in and out
+        let t = t * t * (3.0 - 2.0 * t);
+        camera.position = self.from.position.lerp(self.to.position, t);
+        camera.rotation = self.from.rotation.slerp(self.to.rotation, t);
+        self.elapsed >= self.duration
+    }
+}
+
+impl From<Camera> for CameraUniform {
+    fn from(camera: Camera) -> Self {
+        // the rotation drifts from unit length as rotations accumulate, which would skew the rays,
+        // so normalize it and gram-schmidt the basis to clean up what rounding is left
+        let rotation = camera.rotation.normalize();
+        let forward = (rotation * cgmath::vec3(0.0, 0.0, 1.0)).normalize();
+        let right = rotation * cgmath::vec3(1.0, 0.0, 0.0);
+        let right = (right - forward * forward.dot(right)).normalize();
+        let up = forward.cross(right);
+        Self {
+            position: camera.position,
+            forward,
+            right,
+            up,
+            up_sky_color: crate::scene::clamp_sky_color(
+                (camera.up_sky_color * camera.sky_intensity).into(),
+            )
+            .into(),
+            down_sky_color: crate::scene::clamp_sky_color(
+                (camera.down_sky_color * camera.sky_intensity).into(),
+            )
+            .into(),
+            min_distance: camera.min_distance,
+            max_distance: camera.max_distance,
+            shadow_bias: camera.shadow_bias,
+            eye_separation: camera.eye_separation,
+            vignette_radius: camera.vignette_radius,
+            vignette_intensity: if camera.vignette {
+                camera.vignette_intensity
+            } else {
+                0.0
+            },
+            fog_color: camera.fog_color,
+            fog_density: camera.fog_density,
+            fog_sky: camera.fog_sky as _,
+            exposure: camera.exposure,
+            pixel_aspect: camera.pixel_aspect,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn accumulated_rotations_stay_orthonormal() {
+        let mut camera = Camera::default();
+        for i in 0..10_000 {
+            // the same kind of small steps mouse look and rolling apply every frame
+            let angle = cgmath::Rad(0.001 + (i % 7) as f32 * 0.0003);
+            camera.rotation = camera.rotation * Quaternion::from_angle_y(angle);
+            camera.rotation = camera.rotation * Quaternion::from_angle_x(angle * 0.5);
+            camera.rotation = camera.rotation * Quaternion::from_angle_z(-angle * 0.25);
+        }
+
+        let uniform = CameraUniform::from(camera);
+        let basis = [uniform.forward, uniform.right, uniform.up];
+        for (i, a) in basis.iter().enumerate() {
+            assert!(
+                (a.magnitude() - 1.0).abs() < 1e-5,
+                "{a:?} is not unit length"
+            );
+            for b in &basis[i + 1..] {
+                assert!(a.dot(*b).abs() < 1e-5, "{a:?} and {b:?} are not orthogonal");
+            }
+        }
+    }
+
+    #[test]
+    fn frame_bounds_fits_the_narrower_field_of_view() {
+        let bounds = (cgmath::vec3(-1.0, -1.0, -1.0), cgmath::vec3(1.0, 1.0, 1.0));
+        let radius = 3.0f32.sqrt();
+        let framed_distance = |aspect, pixel_aspect| {
+            let mut camera = Camera {
+                pixel_aspect,
+                ..Camera::default()
+            };
+            camera.frame_bounds(bounds, aspect);
+            camera.position.magnitude()
+        };
+
+        // wide views are limited by the 90 degree vertical field of view
+        let vertical = radius / std::f32::consts::FRAC_PI_4.sin();
+        assert!((framed_distance(1.0, 1.0) - vertical).abs() < 1e-4);
+        assert!((framed_distance(2.0, 1.0) - vertical).abs() < 1e-4);
+
+        // tall views by the horizontal one, which the pixel aspect squeezes too
+        let horizontal = radius / 0.5f32.atan().sin();
+        assert!((framed_distance(0.5, 1.0) - horizontal).abs() < 1e-4);
+        assert!((framed_distance(1.0, 0.5) - horizontal).abs() < 1e-4);
+    }
+
+    #[test]
+    fn set_world_matrix_keeps_the_view_of_a_skewed_matrix() {
+        let mut matrix = Matrix4::from_translation(cgmath::vec3(1.0, 2.0, 3.0))
+            * Matrix4::from(Quaternion::from_angle_y(cgmath::Deg(30.0)))
+            * Matrix4::from_nonuniform_scale(2.0, 0.5, 3.0);
+        // shear the right column towards forward
+        matrix.x += matrix.z * 0.3;
+
+        let mut camera = Camera::default();
+        camera.set_world_matrix(matrix).unwrap();
+        assert_eq!(camera.position, cgmath::vec3(1.0, 2.0, 3.0));
+        assert!((camera.rotation.magnitude() - 1.0).abs() < 1e-5);
+
+        let forward = camera.rotation * cgmath::vec3(0.0, 0.0, 1.0);
+        let expected = matrix.z.truncate().normalize();
+        assert!((forward - expected).magnitude() < 1e-5, "{forward:?}");
+        let up = camera.rotation * cgmath::vec3(0.0, 1.0, 0.0);
+        assert!(
+            (up - cgmath::vec3(0.0, 1.0, 0.0)).magnitude() < 1e-5,
+            "{up:?}"
+        );
+    }
+
+    #[test]
+    fn set_world_matrix_rejects_degenerate_matrices() {
+        let identity = Matrix4::from_scale(1.0);
+        let mut zero_forward = identity;
+        zero_forward.z = cgmath::vec4(0.0, 0.0, 0.0, 0.0);
+        let mut parallel_right = identity;
+        parallel_right.x = identity.z * 2.0;
+        let mut not_finite = identity;
+        not_finite.y.x = f32::NAN;
+        let mut infinite_position = identity;
+        infinite_position.w.x = f32::INFINITY;
+
+        for matrix in [
+            Matrix4::from_scale(0.0),
+            zero_forward,
+            parallel_right,
+            not_finite,
+            infinite_position,
+        ] {
+            let mut camera = Camera::default();
+            assert!(camera.set_world_matrix(matrix).is_err(), "{matrix:?}");
+            // a failed set leaves the camera as it was
+            assert_eq!(camera.rotation, Camera::default().rotation);
+            assert_eq!(camera.position, Camera::default().position);
+        }
+
+        // large but finite is fine, it only has to point somewhere
+        let mut huge = identity;
+        huge.z = cgmath::vec4(3e38, 3e38, 3e38, 0.0);
+        assert!(Camera::default().set_world_matrix(huge).is_ok());
+    }
+}
@@ -0,0 +1,154 @@
+use crate::{camera::Camera, scene::clamp_sky_color};
+use cgmath::InnerSpace;
+
+/// A camera driven from a json file, either a single camera object like the ones in scene files or an
+/// array of them with one per frame. Fields that are left out keep whatever the camera already had
+pub struct CameraFile {
+    frames: Vec<serde_json::Map<String, serde_json::Value>>,
+}
+
+impl CameraFile {
+    pub fn load(path: &std::path::Path) -> Result<Self, String> {
+        let text = std::fs::read_to_string(path).map_err(|error| error.to_string())?;
+        Self::parse(&text)
+    }
+
+    /// Checks every frame up front, so a file that parses can always be applied
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let value: serde_json::Value =
+            serde_json::from_str(text).map_err(|error| error.to_string())?;
+        let frames = match value {
+            serde_json::Value::Array(frames) => frames,
+            frame => vec![frame],
+        };
+        if frames.is_empty() {
+            return Err("the camera track has no frames".into());
+        }
+
+        let known_fields = camera_fields(&Camera::default());
+        let frames = frames
+            .into_iter()
+            .enumerate()
+            .map(|(index, frame)| {
+                let serde_json::Value::Object(frame) = frame else {
+                    return Err(format!("frame {index}: expected a camera object"));
+                };
+                // serde would quietly ignore a misspelled field
+                if let Some(field) = frame
+                    .keys()
+                    .find(|&field| !known_fields.contains_key(field))
+                {
+                    return Err(format!("frame {index}: unknown camera field '{field}'"));
+                }
+                merge(&Camera::default(), &frame)
+                    .map_err(|error| format!("frame {index}: {error}"))?;
+                Ok(frame)
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(Self { frames })
+    }
+
+    pub fn frame_count(&self) -> usize {
+        self.frames.len()
+    }
+
+    /// Overwrites the fields of `camera` that the frame at `index` sets, wrapping around at the end
+    pub fn apply(&self, index: usize, camera: &mut Camera) {
+        if let Ok(merged) = merge(camera, &self.frames[index % self.frames.len()]) {
+            *camera = merged;
+        }
+    }
+}
+
+fn camera_fields(camera: &Camera) -> serde_json::Map<String, serde_json::Value> {
+    match serde_json::to_value(camera) {
+        Ok(serde_json::Value::Object(fields)) => fields,
+        _ => unreachable!("a camera always serializes to an object"),
+    }
+}
+
+fn merge(
+    camera: &Camera,
+    frame: &serde_json::Map<String, serde_json::Value>,
+) -> Result<Camera, String> {
+    let mut fields = camera_fields(camera);
+    fields.extend(frame.clone());
+    let mut camera: Camera = serde_json::from_value(serde_json::Value::Object(fields))
+        .map_err(|error| error.to_string())?;
+    if camera.rotation.magnitude2() == 0.0 {
+        return Err("the rotation is all zeros".into());
+    }
+    // the same cleanup a scene file gets, exporters don't always normalize their rotations
+    camera.up_sky_color = clamp_sky_color(camera.up_sky_color.into()).into();
+    camera.down_sky_color = clamp_sky_color(camera.down_sky_color.into()).into();
+    camera.sky_intensity = camera.sky_intensity.max(0.0);
+    camera.rotation = camera.rotation.normalize();
+    Ok(camera)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn single_camera() {
+        let file = CameraFile::parse(
+            r#"{ "position": [1, 2, 3], "rotation": { "v": [0, 0, 0], "s": 2 }, "exposure": 0.5 }"#,
+        )
+        .unwrap();
+        assert_eq!(file.frame_count(), 1);
+
+        let mut camera = Camera::default();
+        file.apply(0, &mut camera);
+        assert_eq!(camera.position, cgmath::vec3(1.0, 2.0, 3.0));
+        assert_eq!(camera.exposure, 0.5);
+        // rotations get normalized like in a scene file
+        assert_eq!(camera.rotation, cgmath::Quaternion::new(1.0, 0.0, 0.0, 0.0));
+    }
+
+    #[test]
+    fn partial_frames_keep_the_other_fields() {
+        let file =
+            CameraFile::parse(r#"[{ "position": [1, 0, 0] }, { "fog_density": 0.25 }]"#).unwrap();
+        assert_eq!(file.frame_count(), 2);
+
+        let mut camera = Camera {
+            exposure: 2.0,
+            ..Camera::default()
+        };
+        file.apply(0, &mut camera);
+        assert_eq!(camera.position, cgmath::vec3(1.0, 0.0, 0.0));
+        assert_eq!(camera.exposure, 2.0);
+
+        file.apply(1, &mut camera);
+        assert_eq!(camera.position, cgmath::vec3(1.0, 0.0, 0.0));
+        assert_eq!(camera.fog_density, 0.25);
+
+        // wraps around past the last frame
+        file.apply(2, &mut camera);
+        assert_eq!(camera.fog_density, 0.25);
+    }
+
+    #[test]
+    fn rejects_bad_frames() {
+        for (text, error) in [
+            ("[]", "no frames"),
+            ("[1]", "frame 0: expected a camera object"),
+            (
+                r#"[{}, { "positon": [0, 0, 0] }]"#,
+                "frame 1: unknown camera field 'positon'",
+            ),
+            (r#"{ "exposure": "bright" }"#, "frame 0: invalid type"),
+            (
+                r#"{ "rotation": { "v": [0, 0, 0], "s": 0 } }"#,
+                "frame 0: the rotation is all zeros",
+            ),
+            ("{", "EOF"),
+        ] {
+            match CameraFile::parse(text) {
+                Ok(_) => panic!("{text} parsed"),
+                Err(message) => assert!(message.contains(error), "{text}: {message}"),
+            }
+        }
+    }
+}
@@ -0,0 +1,154 @@
+#[derive(Clone, Copy, PartialEq, serde::Serialize, serde::Deserialize)]
+pub enum PngColorSpace {
+    /// Encoded with the srgb transfer function and tagged with an `sRGB` chunk
+    Srgb,
+    /// Stored as-is and tagged with a gamma of 1 through a `gAMA` chunk
+    Linear,
+}
+
+fn linear_to_srgb(value: f32) -> f32 {
+    if value <= 0.0031308 {
+        value * 12.92
+    } else {
+        1.055 * value.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+/// Writes linear float pixels as 8 bit rgba, clamping anything outside 0..1
+pub fn save_png(
+    path: &std::path::Path,
+    (width, height): (usize, usize),
+    pixels: &[[f32; 4]],
+    color_space: PngColorSpace,
+) -> Result<(), String> {
+    if pixels.len() != width * height {
+        return Err("image data has the wrong size".into());
+    }
+    let to_error =
+        |error: &dyn std::fmt::Display| format!("failed to save '{}': {error}", path.display());
+
+    let data = pixels
+        .iter()
+        .flat_map(|&[r, g, b, a]| {
+            let encode = |value: f32| match color_space {
+                PngColorSpace::Srgb => linear_to_srgb(value),
+                PngColorSpace::Linear => value,
+            };
+            // alpha is never gamma encoded
+            [encode(r), encode(g), encode(b), a]
+        })
+        .map(|value| (value.clamp(0.0, 1.0) * 255.0).round() as u8)
+        .collect::<Vec<_>>();
+
+    let file = std::fs::File::create(path).map_err(|error| to_error(&error))?;
+    let mut encoder = png::Encoder::new(std::io::BufWriter::new(file), width as _, height as _);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+    match color_space {
+        PngColorSpace::Srgb => encoder.set_source_srgb(png::SrgbRenderingIntent::Perceptual),
+        PngColorSpace::Linear => encoder.set_source_gamma(png::ScaledFloat::new(1.0)),
+    }
+    let mut writer = encoder.write_header().map_err(|error| to_error(&error))?;
+    writer
+        .write_image_data(&data)
+        .map_err(|error| to_error(&error))
+}
+
+/// Writes linear float pixels as-is, so nothing above 1.0 is lost
+pub fn save_exr(
+    path: &std::path::Path,
+    (width, height): (usize, usize),
+    pixels: &[[f32; 4]],
+) -> Result<(), String> {
+    if pixels.len() != width * height {
+        return Err("image data has the wrong size".into());
+    }
+    exr::prelude::write_rgba_file(path, width, height, |x, y| {
+        let [r, g, b, a] = pixels[y * width + x];
+        (r, g, b, a)
+    })
+    .map_err(|error| format!("failed to save '{}': {error}", path.display()))
+}
+
+/// Writes depths as a 16 bit grayscale png, scaled so `max_distance` is white
+pub fn save_depth_png(
+    path: &std::path::Path,
+    (width, height): (usize, usize),
+    depths: &[f32],
+    max_distance: f32,
+) -> Result<(), String> {
+    if depths.len() != width * height {
+        return Err("image data has the wrong size".into());
+    }
+    let to_error =
+        |error: &dyn std::fmt::Display| format!("failed to save '{}': {error}", path.display());
+
+    let data = depths
+        .iter()
+        .flat_map(|&depth| {
+            let value = (depth / max_distance).clamp(0.0, 1.0);
+            // png stores 16 bit samples big endian
+            ((value * 65535.0).round() as u16).to_be_bytes()
+        })
+        .collect::<Vec<_>>();
+
+    let file = std::fs::File::create(path).map_err(|error| to_error(&error))?;
+    let mut encoder = png::Encoder::new(std::io::BufWriter::new(file), width as _, height as _);
+    encoder.set_color(png::ColorType::Grayscale);
+    encoder.set_depth(png::BitDepth::Sixteen);
+    encoder.set_source_gamma(png::ScaledFloat::new(1.0));
+    let mut writer = encoder.write_header().map_err(|error| to_error(&error))?;
+    writer
+        .write_image_data(&data)
+        .map_err(|error| to_error(&error))
+}
+
+/// Writes depths as-is in world units to a single `Z` channel
+pub fn save_depth_exr(
+    path: &std::path::Path,
+    (width, height): (usize, usize),
+    depths: &[f32],
+) -> Result<(), String> {
+    use exr::prelude::*;
+
+    if depths.len() != width * height {
+        return Err("image data has the wrong size".into());
+    }
+    let channels = SpecificChannels::build()
+        .with_channel("Z")
+        .with_pixel_fn(|position: Vec2<usize>| (depths[position.y() * width + position.x()],));
+    Image::from_channels((width, height), channels)
+        .write()
+        .to_file(path)
+        .map_err(|error| format!("failed to save '{}': {error}", path.display()))
+}
+
+/// Writes normals remapped from -1..1 to 0..1 like a normal map, the sky stays transparent
+pub fn save_normal_png(
+    path: &std::path::Path,
+    size: (usize, usize),
+    normals: &[[f32; 4]],
+) -> Result<(), String> {
+    let pixels = normals
+        .iter()
+        .map(|&[x, y, z, a]| [x * 0.5 + 0.5, y * 0.5 + 0.5, z * 0.5 + 0.5, a])
+        .collect::<Vec<_>>();
+    save_png(path, size, &pixels, PngColorSpace::Linear)
+}
+
+/// Writes a binary P6 ppm by hand, useful for checking colors without going through `image`
+pub fn dump_ppm(
+    path: &std::path::Path,
+    (width, height): (usize, usize),
+    pixels: &[u8],
+) -> Result<(), String> {
+    if pixels.len() != width * height * 4 {
+        return Err("image data has the wrong size".into());
+    }
+    let mut data = format!("P6\n{width} {height}\n255\n").into_bytes();
+    for pixel in pixels.chunks_exact(4) {
+        data.extend_from_slice(&pixel[..3]);
+    }
+    std::fs::write(path, data)
+        .map_err(|error| format!("failed to save '{}': {error}", path.display()))
+}
@@ -0,0 +1,49 @@
+use notify::Watcher;
+
+/// Watches a file for changes, reloading it is left to the caller
+pub struct FileWatcher {
+    path: std::path::PathBuf,
+    // dropping the watcher stops it
+    _watcher: notify::RecommendedWatcher,
+    events: std::sync::mpsc::Receiver<notify::Result<notify::Event>>,
+}
+
+impl FileWatcher {
+    pub fn new(path: &std::path::Path) -> Result<Self, String> {
+        let (sender, events) = std::sync::mpsc::channel();
+        let mut watcher = notify::recommended_watcher(sender).map_err(|error| error.to_string())?;
+        // editors often save by replacing the file, which a watch on the file itself would miss
+        let directory = match path.parent() {
+            Some(parent) if !parent.as_os_str().is_empty() => parent,
+            _ => std::path::Path::new("."),
+        };
+        watcher
+            .watch(directory, notify::RecursiveMode::NonRecursive)
+            .map_err(|error| error.to_string())?;
+        Ok(Self {
+            path: path.to_owned(),
+            _watcher: watcher,
+            events,
+        })
+    }
+
+    pub fn path(&self) -> &std::path::Path {
+        &self.path
+    }
+
+    /// Returns whether the file changed since the last call, without blocking
+    pub fn changed(&self) -> bool {
+        let mut changed = false;
+        for event in self.events.try_iter().flatten() {
+            if !event.kind.is_access()
+                && event
+                    .paths
+                    .iter()
+                    .any(|path| path.file_name() == self.path.file_name())
+            {
+                changed = true;
+            }
+        }
+        changed
+    }
+}
@@ -0,0 +1,41 @@
+/// Reads the sticks of the first connected controller, does nothing if gilrs can't start
+pub struct Gamepad {
+    gilrs: Option<gilrs::Gilrs>,
+}
+
+impl Gamepad {
+    pub fn new() -> Self {
+        Self {
+            gilrs: gilrs::Gilrs::new().ok(),
+        }
+    }
+
+    /// The name of the controller that gets read, if one is connected
+    pub fn name(&self) -> Option<String> {
+        let (_, gamepad) = self.gilrs.as_ref()?.gamepads().next()?;
+        Some(gamepad.name().to_owned())
+    }
+
+    /// The left and right stick with up being positive y, anything inside `deadzone` reads as 0
+    /// and the rest is rescaled so a full tilt is still 1
+    pub fn sticks(&mut self, deadzone: f32) -> Option<([f32; 2], [f32; 2])> {
+        let gilrs = self.gilrs.as_mut()?;
+        // the axis values only update while events get pulled
+        while gilrs.next_event().is_some() {}
+        let (_, gamepad) = gilrs.gamepads().next()?;
+
+        let stick = |x, y| {
+            let value = [gamepad.value(x), gamepad.value(y)];
+            let length = value[0].hypot(value[1]);
+            if length <= deadzone {
+                return [0.0, 0.0];
+            }
+            let scale = ((length - deadzone) / (1.0 - deadzone)).min(1.0) / length;
+            [value[0] * scale, value[1] * scale]
+        };
+        Some((
+            stick(gilrs::Axis::LeftStickX, gilrs::Axis::LeftStickY),
+            stick(gilrs::Axis::RightStickX, gilrs::Axis::RightStickY),
+        ))
+    }
+}
@@ -0,0 +1,100 @@
+use crate::shader_types::Sphere;
+use cgmath::InnerSpace;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Layout {
+    Grid,
+    SphereSurface,
+    RandomBox,
+}
+
+pub struct GenerateOptions {
+    pub layout: Layout,
+    pub count: usize,
+    pub center: cgmath::Vector3<f32>,
+    /// The distance between grid cells, the radius of the big sphere, or the size of the box
+    pub spacing: f32,
+    pub radius: f32,
+    pub color: [f32; 3],
+    pub random_colors: bool,
+    // the same seed always generates the same spheres
+    pub seed: u64,
+}
+
+impl Default for GenerateOptions {
+    fn default() -> Self {
+        Self {
+            layout: Layout::Grid,
+            count: 27,
+            center: cgmath::vec3(0.0, 0.0, 0.0),
+            spacing: 2.0,
+            radius: 0.5,
+            color: [1.0, 1.0, 1.0],
+            random_colors: false,
+            seed: 0,
+        }
+    }
+}
+
+// splitmix64, good enough for placing spheres without pulling in a dependency
+struct Random(u64);
+
+impl Random {
+    fn next_f32(&mut self) -> f32 {
+        self.0 = self.0.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^= z >> 31;
+        // the top 24 bits fit exactly in an f32
+        (z >> 40) as f32 / (1u64 << 24) as f32
+    }
+}
+
+pub fn generate(options: &GenerateOptions) -> Vec<Sphere> {
+    let mut random = Random(options.seed);
+    let count = options.count;
+    (0..count)
+        .map(|i| {
+            let offset = match options.layout {
+                Layout::Grid => {
+                    let side = (1..).find(|side| side * side * side >= count).unwrap();
+                    let cell = cgmath::vec3(
+                        (i % side) as f32,
+                        (i / side % side) as f32,
+                        (i / (side * side)) as f32,
+                    );
+                    let middle = (side - 1) as f32 * 0.5;
+                    (cell - cgmath::vec3(middle, middle, middle)) * options.spacing
+                }
+                Layout::SphereSurface => {
+                    // a fibonacci spiral spreads the points out evenly
+                    let y = 1.0 - (i as f32 + 0.5) / count as f32 * 2.0;
+                    let ring_radius = (1.0 - y * y).sqrt();
+                    let angle = i as f32 * std::f32::consts::PI * (3.0 - 5.0f32.sqrt());
+                    cgmath::vec3(angle.cos() * ring_radius, y, angle.sin() * ring_radius)
+                        .normalize()
+                        * options.spacing
+                }
+                Layout::RandomBox => {
+                    cgmath::vec3(
+                        random.next_f32() - 0.5,
+                        random.next_f32() - 0.5,
+                        random.next_f32() - 0.5,
+                    ) * options.spacing
+                }
+            };
+            let color = if options.random_colors {
+                [random.next_f32(), random.next_f32(), random.next_f32()]
+            } else {
+                crate::scene::clamp_albedo(options.color)
+            };
+            Sphere {
+                position: options.center + offset,
+                radius: options.radius,
+                color: color.into(),
+                ..Default::default()
+            }
+        })
+        .collect()
+}
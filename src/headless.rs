@@ -0,0 +1,116 @@
+use crate::{
+    export,
+    renderer::{RenderOptions, Renderer},
+    scene::Scene,
+};
+
+fn create_device() -> Result<(wgpu::Device, wgpu::Queue), String> {
+    let instance = wgpu::Instance::new(wgpu::InstanceDescriptor::default());
+    let adapter =
+        pollster::block_on(instance.request_adapter(&wgpu::RequestAdapterOptions::default()))
+            .ok_or("could not find a gpu adapter")?;
+    pollster::block_on(adapter.request_device(
+        &wgpu::DeviceDescriptor {
+            label: Some("Headless Device"),
+            features: wgpu::Features::empty(),
+            limits: wgpu::Limits::downlevel_defaults().using_resolution(adapter.limits()),
+        },
+        None,
+    ))
+    .map_err(|error| error.to_string())
+}
+
+/// Renders a single frame of the scene at `scene_path` without opening a window and saves it to `output_path`.
+/// The depth pass is also saved to `depth_path` if there is one, as an exr if it ends in `.exr`, otherwise
+/// as a 16 bit png. World space normals go to `normal_path` the same way, with the sky left at zero
+pub fn render_to_file(
+    scene_path: &std::path::Path,
+    output_path: &std::path::Path,
+    depth_path: Option<&std::path::Path>,
+    normal_path: Option<&std::path::Path>,
+    size @ (width, height): (usize, usize),
+) -> Result<(), String> {
+    if width == 0 || height == 0 {
+        return Err(format!("invalid image size {width}x{height}"));
+    }
+
+    let scene = Scene::load(scene_path)
+        .map_err(|error| format!("failed to load '{}': {error}", scene_path.display()))?;
+    let (device, queue) = create_device()?;
+
+    let mut renderer = Renderer::new(&device, &scene);
+    renderer.resize(&device, size);
+    let submission_index = renderer.render(&device, &queue, &scene, RenderOptions::default());
+    device.poll(wgpu::Maintain::WaitForSubmissionIndex(submission_index));
+
+    let pixels = renderer.read_hdr_texture(&device, &queue);
+    export::save_png(output_path, size, &pixels, export::PngColorSpace::Srgb)?;
+
+    if let Some(depth_path) = depth_path {
+        let depths = renderer.read_depth_texture(&device, &queue);
+        if depth_path
+            .extension()
+            .is_some_and(|extension| extension == "exr")
+        {
+            export::save_depth_exr(depth_path, size, &depths)?;
+        } else {
+            export::save_depth_png(depth_path, size, &depths, scene.camera.max_distance)?;
+        }
+    }
+    if let Some(normal_path) = normal_path {
+        let normals = renderer.read_normal_texture(&device, &queue);
+        if normal_path
+            .extension()
+            .is_some_and(|extension| extension == "exr")
+        {
+            export::save_exr(normal_path, size, &normals)?;
+        } else {
+            export::save_normal_png(normal_path, size, &normals)?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn extreme_sky_colors_render_finite() {
+        // machines without a gpu, like most ci runners, can't run this
+        let (device, queue) = match create_device() {
+            Ok(device) => device,
+            Err(error) => {
+                eprintln!("skipping extreme_sky_colors_render_finite: {error}");
+                return;
+            }
+        };
+        for (color, intensity) in [
+            (f32::NAN, 1.0),
+            (f32::INFINITY, 1.0),
+            (f32::NEG_INFINITY, 1.0),
+            (1e30, 1.0),
+            (-1e30, 1.0),
+            (1e30, 1e30),
+            (1.0, f32::NAN),
+            (f32::INFINITY, 0.0),
+        ] {
+            let mut scene = Scene::default();
+            scene.camera.up_sky_color = cgmath::vec3(color, color, color);
+            scene.camera.down_sky_color = cgmath::vec3(color, 0.5, color);
+            scene.camera.sky_intensity = intensity;
+
+            let mut renderer = Renderer::new(&device, &scene);
+            renderer.resize(&device, (32, 32));
+            let submission_index =
+                renderer.render(&device, &queue, &scene, RenderOptions::default());
+            device.poll(wgpu::Maintain::WaitForSubmissionIndex(submission_index));
+
+            let pixels = renderer.read_hdr_texture(&device, &queue);
+            assert!(
+                pixels.iter().flatten().all(|channel| channel.is_finite()),
+                "sky color {color} with intensity {intensity} rendered a non-finite pixel"
+            );
+        }
+    }
+}
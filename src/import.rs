@@ -0,0 +1,110 @@
+use crate::{camera::Camera, scene::Scene, shader_types::Sphere};
+use cgmath::{EuclideanSpace, InnerSpace, Matrix4, SquareMatrix, Transform};
+
+// gltf looks down -z while our camera looks down +z, so everything gets mirrored along z
+fn mirror_z() -> Matrix4<f32> {
+    Matrix4::from_nonuniform_scale(1.0, 1.0, -1.0)
+}
+
+/// Builds a scene from a gltf file, with one sphere around each mesh primitive's bounding box,
+/// keeps `camera` if the file has no camera node
+pub fn import_gltf(path: &std::path::Path, camera: Camera) -> Result<Scene, String> {
+    let gltf = gltf::Gltf::open(path).map_err(|error| error.to_string())?;
+    let gltf_scene = gltf
+        .default_scene()
+        .or_else(|| gltf.scenes().next())
+        .ok_or("the file has no scenes")?;
+
+    let mut scene = Scene {
+        camera,
+        spheres: vec![],
+        ..Default::default()
+    };
+    let mut found_camera = false;
+    for node in gltf_scene.nodes() {
+        import_node(&node, Matrix4::identity(), &mut scene, &mut found_camera)?;
+    }
+    Ok(scene)
+}
+
+fn import_node(
+    node: &gltf::Node,
+    parent_transform: Matrix4<f32>,
+    scene: &mut Scene,
+    found_camera: &mut bool,
+) -> Result<(), String> {
+    let transform = parent_transform * Matrix4::from(node.transform().matrix());
+
+    if let Some(mesh) = node.mesh() {
+        // a sphere only scales uniformly, so use the largest axis scale to still cover the box
+        let scale = transform
+            .x
+            .truncate()
+            .magnitude()
+            .max(transform.y.truncate().magnitude())
+            .max(transform.z.truncate().magnitude());
+        for primitive in mesh.primitives() {
+            let bounds = primitive.bounding_box();
+            let min = cgmath::Vector3::from(bounds.min);
+            let max = cgmath::Vector3::from(bounds.max);
+            let center = transform.transform_point(cgmath::Point3::from_vec((min + max) * 0.5));
+            let [r, g, b, a] = primitive
+                .material()
+                .pbr_metallic_roughness()
+                .base_color_factor();
+            scene.spheres.push(Sphere {
+                position: mirror_z().transform_point(center).to_vec(),
+                radius: (max - min).magnitude() * 0.5 * scale,
+                color: (r, g, b).into(),
+                opacity: a,
+                ..Default::default()
+            });
+        }
+    }
+
+    // only the first camera is used
+    if node.camera().is_some() && !*found_camera {
+        scene
+            .camera
+            .set_world_matrix(mirror_z() * transform * mirror_z())
+            .map_err(|error| format!("the camera node's transform is unusable: {error}"))?;
+        *found_camera = true;
+    }
+
+    for child in node.children() {
+        import_node(&child, transform, scene, found_camera)?;
+    }
+    Ok(())
+}
+
+/// Decodes a png into tightly packed 8 bit rgba rows, whatever its color type and bit depth
+pub fn load_png(path: &std::path::Path) -> Result<((usize, usize), Vec<u8>), String> {
+    let file = std::fs::File::open(path).map_err(|error| error.to_string())?;
+    let mut decoder = png::Decoder::new(std::io::BufReader::new(file));
+    decoder.set_transformations(png::Transformations::normalize_to_color8());
+    let mut reader = decoder.read_info().map_err(|error| error.to_string())?;
+    let mut data = vec![0; reader.output_buffer_size()];
+    let info = reader
+        .next_frame(&mut data)
+        .map_err(|error| error.to_string())?;
+    data.truncate(info.buffer_size());
+
+    let pixels = match info.color_type {
+        png::ColorType::Rgba => data,
+        png::ColorType::Rgb => data
+            .chunks_exact(3)
+            .flat_map(|pixel| [pixel[0], pixel[1], pixel[2], 255])
+            .collect(),
+        png::ColorType::GrayscaleAlpha => data
+            .chunks_exact(2)
+            .flat_map(|pixel| [pixel[0], pixel[0], pixel[0], pixel[1]])
+            .collect(),
+        png::ColorType::Grayscale => data
+            .iter()
+            .flat_map(|&value| [value, value, value, 255])
+            .collect(),
+        // expanded into rgb by the transformations above
+        png::ColorType::Indexed => return Err("unsupported indexed png".into()),
+    };
+    Ok(((info.width as _, info.height as _), pixels))
+}
@@ -0,0 +1,206 @@
+use eframe::egui::{self, Key, KeyboardShortcut, ModifierNames, Modifiers};
+
+const NAMES: ModifierNames<'static> = ModifierNames {
+    concat: " + ",
+    ..ModifierNames::NAMES
+};
+
+/// Every key the app responds to, the input handling and the help overlay both read from this so
+/// they can't disagree about what a key does
+#[derive(Clone, Copy)]
+pub struct KeyBindings {
+    // held keys, these ignore modifiers so moving and looking can be combined freely
+    pub forward: Key,
+    pub back: Key,
+    pub left: Key,
+    pub right: Key,
+    pub up: Key,
+    /// A modifier rather than a key, egui doesn't report ctrl on its own as a key press
+    pub down: Modifiers,
+    pub look_left: Key,
+    pub look_right: Key,
+    pub look_up: Key,
+    pub look_down: Key,
+    pub roll_left: Key,
+    pub roll_right: Key,
+
+    pub next_sphere: KeyboardShortcut,
+    pub previous_sphere: KeyboardShortcut,
+    /// Moves the selection by this many `nudge_step`s
+    pub nudge_horizontal: [(KeyboardShortcut, [f32; 3]); 4],
+    pub nudge_vertical: [(KeyboardShortcut, [f32; 3]); 2],
+    pub bookmarks: [Key; 8],
+    /// Held together with a bookmark key to save the camera instead of going to it
+    pub save_bookmark: Modifiers,
+    pub undo: KeyboardShortcut,
+    pub exit: KeyboardShortcut,
+    pub help: KeyboardShortcut,
+}
+
+impl Default for KeyBindings {
+    fn default() -> Self {
+        const ALT_SHIFT: Modifiers = Modifiers::ALT.plus(Modifiers::SHIFT);
+        Self {
+            forward: Key::W,
+            back: Key::S,
+            left: Key::A,
+            right: Key::D,
+            up: Key::Space,
+            down: Modifiers::CTRL,
+            look_left: Key::ArrowLeft,
+            look_right: Key::ArrowRight,
+            look_up: Key::ArrowUp,
+            look_down: Key::ArrowDown,
+            roll_left: Key::Q,
+            roll_right: Key::E,
+
+            next_sphere: KeyboardShortcut::new(Modifiers::NONE, Key::Tab),
+            previous_sphere: KeyboardShortcut::new(Modifiers::SHIFT, Key::Tab),
+            nudge_horizontal: [
+                (
+                    KeyboardShortcut::new(Modifiers::ALT, Key::ArrowRight),
+                    [1.0, 0.0, 0.0],
+                ),
+                (
+                    KeyboardShortcut::new(Modifiers::ALT, Key::ArrowLeft),
+                    [-1.0, 0.0, 0.0],
+                ),
+                (
+                    KeyboardShortcut::new(Modifiers::ALT, Key::ArrowUp),
+                    [0.0, 0.0, 1.0],
+                ),
+                (
+                    KeyboardShortcut::new(Modifiers::ALT, Key::ArrowDown),
+                    [0.0, 0.0, -1.0],
+                ),
+            ],
+            nudge_vertical: [
+                (
+                    KeyboardShortcut::new(ALT_SHIFT, Key::ArrowUp),
+                    [0.0, 1.0, 0.0],
+                ),
+                (
+                    KeyboardShortcut::new(ALT_SHIFT, Key::ArrowDown),
+                    [0.0, -1.0, 0.0],
+                ),
+            ],
+            bookmarks: [
+                Key::Num1,
+                Key::Num2,
+                Key::Num3,
+                Key::Num4,
+                Key::Num5,
+                Key::Num6,
+                Key::Num7,
+                Key::Num8,
+            ],
+            save_bookmark: Modifiers::SHIFT,
+            undo: KeyboardShortcut::new(Modifiers::COMMAND, Key::Z),
+            exit: KeyboardShortcut::new(Modifiers::NONE, Key::Escape),
+            help: KeyboardShortcut::new(Modifiers::NONE, Key::H),
+        }
+    }
+}
+
+impl KeyBindings {
+    /// Whether all of `modifiers` are held, other modifiers being held too doesn't matter
+    pub fn held(input: &egui::InputState, modifiers: Modifiers) -> bool {
+        modifiers.any() && input.modifiers.plus(modifiers) == input.modifiers
+    }
+
+    /// Whether `key` is held for looking around, rather than as part of a held nudge shortcut
+    pub fn look_down(&self, input: &egui::InputState, key: Key) -> bool {
+        input.key_down(key)
+            && !self
+                .nudge_horizontal
+                .iter()
+                .chain(&self.nudge_vertical)
+                .any(|(shortcut, _)| shortcut.key == key && Self::held(input, shortcut.modifiers))
+    }
+
+    /// Like "Ctrl + Z", spelled the way the help overlay lists it
+    pub fn format(shortcut: KeyboardShortcut, is_mac: bool) -> String {
+        shortcut.format(&NAMES, is_mac)
+    }
+
+    /// The rows of the help overlay, as `(keys, action)`
+    pub fn help(&self, is_mac: bool) -> Vec<(String, &'static str)> {
+        let shortcut = |shortcut| Self::format(shortcut, is_mac);
+        let shortcuts = |shortcuts: &[(KeyboardShortcut, [f32; 3])]| {
+            shortcuts
+                .iter()
+                .map(|&(key, _)| shortcut(key))
+                .collect::<Vec<_>>()
+                .join(" / ")
+        };
+        let keys = |keys: &[Key]| {
+            keys.iter()
+                .map(|key| key.name())
+                .collect::<Vec<_>>()
+                .join(" / ")
+        };
+        let bookmarks = format!(
+            "{} - {}",
+            self.bookmarks[0].name(),
+            self.bookmarks[self.bookmarks.len() - 1].name()
+        );
+        vec![
+            (
+                keys(&[self.forward, self.left, self.back, self.right]),
+                "Move",
+            ),
+            (
+                format!("{} / {}", self.up.name(), NAMES.format(&self.down, is_mac)),
+                "Move up / down",
+            ),
+            (
+                keys(&[
+                    self.look_left,
+                    self.look_right,
+                    self.look_up,
+                    self.look_down,
+                ]),
+                "Look around",
+            ),
+            (
+                "Right Drag".into(),
+                "Look around, or rotate around the orbit target",
+            ),
+            (keys(&[self.roll_left, self.roll_right]), "Roll"),
+            ("Click".into(), "Select a sphere"),
+            (
+                "Ctrl + Click".into(),
+                "Add or remove a sphere from the selection",
+            ),
+            ("Shift + Click".into(), "Probe the ray through a pixel"),
+            (
+                format!(
+                    "{} / {}",
+                    shortcut(self.next_sphere),
+                    shortcut(self.previous_sphere)
+                ),
+                "Select the next / previous sphere",
+            ),
+            (
+                shortcuts(&self.nudge_horizontal),
+                "Nudge the selection along x / z",
+            ),
+            (
+                shortcuts(&self.nudge_vertical),
+                "Nudge the selection along y",
+            ),
+            (bookmarks.clone(), "Go to a camera bookmark"),
+            (
+                format!(
+                    "{}{}{bookmarks}",
+                    NAMES.format(&self.save_bookmark, is_mac),
+                    NAMES.concat
+                ),
+                "Save a camera bookmark",
+            ),
+            (shortcut(self.undo), "Undo a sphere edit"),
+            (shortcut(self.exit), "Quit, if Escape To Exit is on"),
+            (shortcut(self.help), "Show this help"),
+        ]
+    }
+}
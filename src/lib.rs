@@ -1,492 +1,3053 @@
-use cgmath::{Quaternion, Rotation3};
+use cgmath::{InnerSpace, Matrix, Matrix4, Rotation3};
 use eframe::egui;
-use encase::{ArrayLength, ShaderSize, ShaderType, StorageBuffer, UniformBuffer};
-use wgpu::util::DeviceExt;
-
-#[derive(Clone, Copy)]
-struct Camera {
-    position: cgmath::Vector3<f32>,
-    rotation: Quaternion<f32>,
-    up_sky_color: cgmath::Vector3<f32>,
-    down_sky_color: cgmath::Vector3<f32>,
-    min_distance: f32,
-    max_distance: f32,
+use key_bindings::KeyBindings;
+use shader_types::Sphere;
+use std::io::Write;
+
+// `encase`'s derive emits never-called `check` functions, which newer compilers flag as dead code
+#[allow(dead_code)]
+mod shader_types;
+
+mod camera;
+mod camera_file;
+mod export;
+mod file_watcher;
+#[cfg(feature = "gamepad")]
+mod gamepad;
+mod generate;
+mod headless;
+mod import;
+mod key_bindings;
+mod obj;
+mod probe;
+mod renderer;
+mod scene;
+mod sphere;
+mod sphere_csv;
+mod turntable;
+
+pub use camera::Camera;
+pub use headless::render_to_file;
+pub use renderer::{RenderOptions, Renderer};
+pub use scene::Scene;
+
+// parses 16 whitespace separated floats in row-major order
+fn parse_matrix(text: &str) -> Result<Matrix4<f32>, String> {
+    let values = text
+        .split_whitespace()
+        .map(|value| {
+            value
+                .parse::<f32>()
+                .ok()
+                .filter(|value| value.is_finite())
+                .ok_or_else(|| format!("'{value}' is not a finite number"))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    if values.len() != 16 {
+        return Err(format!("expected 16 numbers but got {}", values.len()));
+    }
+    let row = |i: usize| {
+        cgmath::vec4(
+            values[i * 4],
+            values[i * 4 + 1],
+            values[i * 4 + 2],
+            values[i * 4 + 3],
+        )
+    };
+    Ok(Matrix4::from_cols(row(0), row(1), row(2), row(3)).transpose())
+}
+
+// parses `#RRGGBB`, the leading `#` is optional
+fn parse_hex_color(text: &str) -> Option<egui::Color32> {
+    let hex = text.trim().trim_start_matches('#');
+    if hex.len() != 6 {
+        return None;
+    }
+    let channel = |i: usize| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok();
+    Some(egui::Color32::from_rgb(
+        channel(0)?,
+        channel(2)?,
+        channel(4)?,
+    ))
 }
 
-#[derive(Clone, Copy, ShaderType)]
-struct CameraUniform {
-    position: cgmath::Vector3<f32>,
-    forward: cgmath::Vector3<f32>,
-    right: cgmath::Vector3<f32>,
-    up: cgmath::Vector3<f32>,
-    up_sky_color: cgmath::Vector3<f32>,
-    down_sky_color: cgmath::Vector3<f32>,
-    min_distance: f32,
-    max_distance: f32,
+// a color picker with an `#RRGGBB` field next to it, hex codes are srgb like the picker shows them
+fn color_edit(ui: &mut egui::Ui, id_source: impl std::hash::Hash, color: &mut [f32; 3]) {
+    egui::color_picker::color_edit_button_rgb(ui, color);
+
+    let id = ui.make_persistent_id(id_source);
+    let current = egui::Color32::from(egui::Rgba::from_rgb(color[0], color[1], color[2]));
+    // the text is only kept while editing, so half typed codes dont get replaced every frame
+    let mut text = ui
+        .data_mut(|data| data.get_temp::<String>(id))
+        .unwrap_or_else(|| format!("#{:02X}{:02X}{:02X}", current.r(), current.g(), current.b()));
+    let response = ui.add(egui::TextEdit::singleline(&mut text).desired_width(64.0));
+    if response.changed() {
+        // invalid codes are ignored and leave the color as it was
+        if let Some(parsed) = parse_hex_color(&text) {
+            let rgba = egui::Rgba::from(parsed);
+            *color = [rgba.r(), rgba.g(), rgba.b()];
+        }
+    }
+    if response.has_focus() {
+        ui.data_mut(|data| data.insert_temp(id, text));
+    } else {
+        ui.data_mut(|data| data.remove::<String>(id));
+    }
 }
 
-#[derive(Clone, Copy, ShaderType)]
-struct Sphere {
-    position: cgmath::Vector3<f32>,
-    radius: f32,
-    color: cgmath::Vector3<f32>,
+// the color picker can hand back values outside 0..1, which isn't a valid albedo
+fn albedo_edit(ui: &mut egui::Ui, id_source: impl std::hash::Hash, color: &mut [f32; 3]) {
+    color_edit(ui, id_source, color);
+    *color = scene::clamp_albedo(*color);
 }
 
-impl Default for Sphere {
+/// A vertical line across `rect` that can be dragged sideways, `split` is where it is from 0 to 1
+fn split_line_ui(
+    ui: &mut egui::Ui,
+    rect: egui::Rect,
+    split: &mut f32,
+    id_source: impl std::hash::Hash,
+) {
+    let split_x = egui::lerp(rect.x_range(), *split);
+    let handle = ui.interact(
+        egui::Rect::from_x_y_ranges(split_x - 4.0..=split_x + 4.0, rect.y_range()),
+        ui.id().with(id_source),
+        egui::Sense::drag(),
+    );
+    if handle.dragged() {
+        *split = (*split + handle.drag_delta().x / rect.width()).clamp(0.0, 1.0);
+    }
+    let stroke_color = if handle.hovered() || handle.dragged() {
+        egui::Color32::YELLOW
+    } else {
+        egui::Color32::WHITE
+    };
+    ui.painter().vline(
+        split_x,
+        rect.y_range(),
+        egui::Stroke::new(2.0, stroke_color),
+    );
+}
+
+// what a scene is called in tabs, just the file name
+fn scene_name(scene_path: &str) -> String {
+    std::path::Path::new(scene_path)
+        .file_name()
+        .map_or("Untitled".into(), |name| {
+            name.to_string_lossy().into_owned()
+        })
+}
+
+// name, up sky color, down sky color
+const SKY_PRESETS: &[(&str, [f32; 3], [f32; 3])] = &[
+    ("Noon", [1.0, 1.0, 1.0], [0.5, 0.7, 1.0]),
+    ("Sunset", [1.0, 0.6, 0.3], [0.3, 0.3, 0.6]),
+    ("Overcast", [0.75, 0.75, 0.75], [0.55, 0.57, 0.6]),
+    ("Night", [0.05, 0.05, 0.1], [0.0, 0.0, 0.02]),
+];
+
+// name, checkerboard, native dpi, max texture size, ssaa, edge aa samples where 0 turns edge aa off
+const QUALITY_PRESETS: &[(&str, bool, bool, u32, u32, u32)] = &[
+    ("Performance", true, false, 1024, 1, 0),
+    ("Balanced", false, false, 2048, 1, 0),
+    ("Quality", false, true, 4096, 2, 8),
+];
+
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+struct Settings {
+    panel_on_right: bool,
+    // asks first if any scene has unsaved changes
+    escape_to_exit: bool,
+    // the shader loops over every sphere for each march step, so too many can hang the gpu
+    max_spheres: usize,
+    snap_to_grid: bool,
+    grid_size: f32,
+    // how far alt + arrow keys move the selection
+    nudge_step: f32,
+    stereo: bool,
+    tiled_dispatch: bool,
+    tile_size: usize,
+    edge_aa: bool,
+    edge_aa_samples: u32,
+    // relative to the depth of the pixel
+    edge_aa_threshold: f32,
+    // renders this many times larger along each axis and averages it down, 1 is off
+    ssaa: u32,
+    clip_overlay: bool,
+    // in world units
+    clip_margin: f32,
+    grid: bool,
+    // in world units
+    grid_spacing: f32,
+    grid_color: [f32; 3],
+    // in hz
+    fixed_update_rate: u32,
+    dither: bool,
+    camera_relative: bool,
+    checkerboard: bool,
+    // skips waiting for each frame to finish, at the cost of exact render timings
+    max_fps: bool,
+    show_sphere_labels: bool,
+    // renders one texel per physical pixel instead of one per point
+    native_dpi: bool,
+    // the longest side of the render texture, in pixels
+    max_texture_size: u32,
+    auto_exposure: bool,
+    false_color: bool,
+    // only does anything once a backplate image has been loaded
+    backplate: bool,
+    // how quickly auto exposure catches up, higher is faster
+    exposure_adaptation_speed: f32,
+    camera_bookmarks: [Option<camera::Camera>; 8],
+    // in seconds
+    bookmark_tween_duration: f32,
+    throttle_when_unfocused: bool,
+    // 0 pauses rendering entirely until the window gets focus back
+    unfocused_fps: f32,
+    png_color_space: export::PngColorSpace,
+    // how fast a watched camera track plays, in frames per second
+    camera_track_fps: f32,
+    // latitude bands per sphere in obj exports
+    obj_subdivisions: u32,
+    obj_merge: bool,
+    // scales the render up to the viewport with nearest neighbor instead of linear filtering
+    nearest_filter: bool,
+    // how far a stick has to move before it does anything, from 0 to 1
+    #[cfg(feature = "gamepad")]
+    gamepad_deadzone: f32,
+    // in degrees per second at full tilt
+    #[cfg(feature = "gamepad")]
+    gamepad_look_speed: f32,
+    // in units per second at full tilt
+    #[cfg(feature = "gamepad")]
+    gamepad_move_speed: f32,
+}
+
+impl Default for Settings {
     fn default() -> Self {
         Self {
-            position: (0.0, 0.0, 0.0).into(),
-            radius: 1.0,
-            color: (1.0, 1.0, 1.0).into(),
+            panel_on_right: false,
+            escape_to_exit: false,
+            max_spheres: 256,
+            snap_to_grid: false,
+            grid_size: 0.5,
+            nudge_step: 0.1,
+            stereo: false,
+            tiled_dispatch: false,
+            tile_size: 256,
+            edge_aa: false,
+            edge_aa_samples: 4,
+            edge_aa_threshold: 0.05,
+            ssaa: 1,
+            clip_overlay: false,
+            clip_margin: 0.1,
+            grid: false,
+            grid_spacing: 1.0,
+            grid_color: [0.5, 0.5, 0.5],
+            fixed_update_rate: 60,
+            dither: false,
+            camera_relative: false,
+            checkerboard: false,
+            max_fps: false,
+            show_sphere_labels: false,
+            native_dpi: true,
+            max_texture_size: 4096,
+            auto_exposure: false,
+            false_color: false,
+            backplate: false,
+            exposure_adaptation_speed: 1.0,
+            camera_bookmarks: Default::default(),
+            bookmark_tween_duration: 1.0,
+            throttle_when_unfocused: true,
+            unfocused_fps: 5.0,
+            png_color_space: export::PngColorSpace::Srgb,
+            camera_track_fps: 24.0,
+            obj_subdivisions: 16,
+            obj_merge: false,
+            nearest_filter: false,
+            #[cfg(feature = "gamepad")]
+            gamepad_deadzone: 0.15,
+            #[cfg(feature = "gamepad")]
+            gamepad_look_speed: 120.0,
+            #[cfg(feature = "gamepad")]
+            gamepad_move_speed: 2.0,
         }
     }
 }
 
-#[derive(Clone, ShaderType)]
-struct SpheresBuffer {
-    sphere_count: ArrayLength,
-    #[size(runtime)]
+/// The limits that matter for big scenes and large dispatches
+fn gpu_limits(limits: &wgpu::Limits) -> [(&'static str, u32); 9] {
+    [
+        (
+            "Max Storage Buffer Size",
+            limits.max_storage_buffer_binding_size,
+        ),
+        (
+            "Max Uniform Buffer Size",
+            limits.max_uniform_buffer_binding_size,
+        ),
+        ("Max Texture Size", limits.max_texture_dimension_2d),
+        (
+            "Max Workgroup Invocations",
+            limits.max_compute_invocations_per_workgroup,
+        ),
+        ("Max Workgroup Size X", limits.max_compute_workgroup_size_x),
+        ("Max Workgroup Size Y", limits.max_compute_workgroup_size_y),
+        ("Max Workgroup Size Z", limits.max_compute_workgroup_size_z),
+        (
+            "Max Workgroups Per Dimension",
+            limits.max_compute_workgroups_per_dimension,
+        ),
+        (
+            "Max Workgroup Storage",
+            limits.max_compute_workgroup_storage_size,
+        ),
+    ]
+}
+
+type FixedUpdateCallback = Box<dyn FnMut(&mut Scene, f64)>;
+
+/// What undo puts back, the camera position only for edits that moved it along with the spheres
+struct UndoEntry {
     spheres: Vec<Sphere>,
+    // so undoing a reorder or delete keeps the names and editor state on the right spheres
+    sphere_names: Vec<String>,
+    sphere_ids: Vec<u64>,
+    camera_position: Option<cgmath::Vector3<f32>>,
 }
 
-impl From<Camera> for CameraUniform {
-    fn from(camera: Camera) -> Self {
-        let forward = camera.rotation * cgmath::vec3(0.0, 0.0, 1.0);
-        let right = camera.rotation * cgmath::vec3(1.0, 0.0, 0.0);
-        let up = camera.rotation * cgmath::vec3(0.0, 1.0, 0.0);
+/// The parts of a scene that get saved, as of some earlier frame
+struct SceneSnapshot {
+    camera: Camera,
+    cameras: Vec<scene::NamedCamera>,
+    active_camera: usize,
+    spheres: Vec<Sphere>,
+    sphere_names: Vec<String>,
+}
+
+impl SceneSnapshot {
+    fn new(scene: &Scene) -> Self {
         Self {
-            position: camera.position,
-            forward,
-            right,
-            up,
-            up_sky_color: camera.up_sky_color,
-            down_sky_color: camera.down_sky_color,
-            min_distance: camera.min_distance,
-            max_distance: camera.max_distance,
+            camera: scene.camera,
+            cameras: scene.cameras.clone(),
+            active_camera: scene.active_camera,
+            spheres: scene.spheres.clone(),
+            sphere_names: scene.sphere_names.clone(),
         }
     }
+
+    fn matches(&self, scene: &Scene) -> bool {
+        self.camera == scene.camera
+            && self.cameras == scene.cameras
+            && self.active_camera == scene.active_camera
+            && self.spheres == scene.spheres
+            && self.sphere_names == scene.sphere_names
+    }
+}
+
+/// Everything that belongs to one open scene, the active tab's copy is out of date since
+/// its state lives in the `App` fields until another tab is switched to
+#[derive(Default)]
+struct SceneTab {
+    scene_path: String,
+    scene: Scene,
+    selected_spheres: std::collections::BTreeSet<usize>,
+    undo_stack: Vec<UndoEntry>,
+    dirty: bool,
 }
 
 pub struct App {
     last_frame_time: std::time::Instant,
     fixed_update_time: f64, // change this to std::time::Duration at some point
     last_frame_update_duration: std::time::Duration,
+    // only tracked in max fps mode, where the render time is averaged from the time between renders
+    last_render_start: Option<std::time::Instant>,
+    // what auto exposure is heading towards, from the last luminance that came back
+    target_exposure: Option<f32>,
     last_fixed_update_duration: std::time::Duration,
-    texture_size: (usize, usize),
-    texture_bind_group: wgpu::BindGroup,
+    renderer: Renderer,
     texture_id: egui::TextureId,
-    pipeline: wgpu::ComputePipeline,
-    camera: Camera,
-    camera_buffer: wgpu::Buffer,
-    camera_bind_group: wgpu::BindGroup,
-    spheres_storage: SpheresBuffer,
-    spheres_buffer: wgpu::Buffer,
-    spheres_bind_group: wgpu::BindGroup,
-    spheres_buffer_size: usize,
+    // what `texture_id` was registered with, it gets registered again when the setting changes
+    texture_filter: wgpu::FilterMode,
+    scene: Scene,
+    camera_matrix_text: String,
+    camera_matrix_error: Option<String>,
+    camera_text: String,
+    camera_text_error: Option<String>,
+    frame_stats_path: String,
+    frame_stats_file: Option<std::io::BufWriter<std::fs::File>>,
+    frame_stats_error: Option<String>,
+    settings: Settings,
+    banner: Option<String>,
+    scene_path: String,
+    selected_spheres: std::collections::BTreeSet<usize>,
+    // whether the scene changed since it was last loaded or saved
+    scene_dirty: bool,
+    // the scene as of the last frame, so edits from any widget get noticed
+    last_scene: SceneSnapshot,
+    // set when closing was stopped because of unsaved changes
+    show_exit_confirm: bool,
+    // the user chose to quit anyway, so closing isn't stopped again
+    allowed_to_close: bool,
+    tabs: Vec<SceneTab>,
+    active_tab: usize,
+    // set when the selection changes from the keyboard, so the editor can bring it into view
+    scroll_to_selected: bool,
+    gpu_errors: std::sync::mpsc::Receiver<String>,
+    // the most recent error, kept after the banner is dismissed
+    last_error: Option<String>,
+    generate_options: generate::GenerateOptions,
+    turntable: Option<turntable::Turntable>,
+    on_fixed_update: Option<FixedUpdateCallback>,
+    turntable_frames: usize,
+    // the frozen "A" render, shown left of `compare_split` with the live render on the right
+    compare_texture: Option<(wgpu::Texture, egui::TextureId)>,
+    // 0 to 1 across the viewport
+    compare_split: f32,
+    // the index of the camera shown right of `camera_split`, `None` turns split screen off
+    split_camera: Option<usize>,
+    // 0 to 1 across the viewport
+    camera_split: f32,
+    // the trace of the last shift clicked pixel
+    probe: Option<String>,
+    show_shader_source: bool,
+    key_bindings: KeyBindings,
+    show_key_help: bool,
+    // the sphere whose header is being dragged to reorder it
+    dragged_sphere: Option<usize>,
+    // snapshots of the spheres before each bulk edit
+    undo_stack: Vec<UndoEntry>,
+    bulk_color: [f32; 3],
+    bulk_offset: cgmath::Vector3<f32>,
+    export_path: String,
+    backplate_path: String,
+    sphere_csv_path: String,
+    sphere_csv_watcher: Option<file_watcher::FileWatcher>,
+    camera_file_path: String,
+    camera_file_watcher: Option<file_watcher::FileWatcher>,
+    // the last version of the watched file that loaded, kept when a newer one is broken
+    camera_file: Option<camera_file::CameraFile>,
+    // how long a camera track has been playing, in seconds
+    camera_track_time: f64,
+    // which frame of the track was applied last, so the camera is only touched when it changes
+    camera_track_frame: Option<usize>,
+    // only set while hot reloading the shader
+    shader_watcher: Option<file_watcher::FileWatcher>,
+    // free-fly when this is `None`
+    orbit: Option<camera::Orbit>,
+    camera_tween: Option<camera::CameraTween>,
+    // ignores mouse and keyboard camera controls, the camera ui still works
+    camera_locked: bool,
+    recenter_origin: cgmath::Vector3<f32>,
+    #[cfg(feature = "gamepad")]
+    gamepad: gamepad::Gamepad,
 }
 
 impl App {
-    pub fn new(cc: &eframe::CreationContext) -> Self {
+    /// Calls `on_fixed_update` with the scene and the timestep in seconds on every fixed update,
+    /// for animating or otherwise changing the scene from outside the crate
+    pub fn with_on_fixed_update(
+        mut self,
+        on_fixed_update: impl FnMut(&mut Scene, f64) + 'static,
+    ) -> Self {
+        self.on_fixed_update = Some(Box::new(on_fixed_update));
+        self
+    }
+
+    pub fn new(cc: &eframe::CreationContext, scene_path: Option<&std::path::Path>) -> Self {
         let render_state = cc.wgpu_render_state.as_ref().unwrap();
 
-        let (width, height) = (1usize, 1usize);
-        let texture_size = wgpu::Extent3d {
-            width: width as _,
-            height: height as _,
-            depth_or_array_layers: 1,
+        let mut banner = None;
+        let scene = match scene_path {
+            Some(path) => Scene::load(path).unwrap_or_else(|error| {
+                banner = Some(format!("Failed to load '{}': {error}", path.display()));
+                Scene::default()
+            }),
+            None => Scene::default(),
         };
 
-        let texture = render_state
+        let renderer = Renderer::new(&render_state.device, &scene);
+        let last_scene = SceneSnapshot::new(&scene);
+
+        // without a handler wgpu panics on errors like failed allocations or invalid shaders
+        let (gpu_error_sender, gpu_errors) = std::sync::mpsc::channel();
+        render_state
             .device
-            .create_texture(&wgpu::TextureDescriptor {
-                size: texture_size,
-                mip_level_count: 1,
-                sample_count: 1,
-                dimension: wgpu::TextureDimension::D2,
-                format: wgpu::TextureFormat::Rgba8Unorm,
-                usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::STORAGE_BINDING,
-                label: Some("texture"),
-                view_formats: &[],
-            });
+            .on_uncaptured_error(Box::new(move |error| {
+                let _ = gpu_error_sender.send(error.to_string());
+            }));
 
         let texture_id = render_state.renderer.write().register_native_texture(
             &render_state.device,
-            &texture.create_view(&wgpu::TextureViewDescriptor {
-                ..Default::default()
-            }),
+            &renderer
+                .texture()
+                .create_view(&wgpu::TextureViewDescriptor::default()),
             wgpu::FilterMode::Linear,
         );
 
-        let shader = render_state
-            .device
-            .create_shader_module(wgpu::include_wgsl!("./shader.wgsl"));
+        Self {
+            last_frame_time: std::time::Instant::now(),
+            fixed_update_time: 0.0,
+            last_frame_update_duration: std::time::Duration::ZERO,
+            last_render_start: None,
+            target_exposure: None,
+            last_fixed_update_duration: std::time::Duration::ZERO,
+            renderer,
+            texture_id,
+            texture_filter: wgpu::FilterMode::Linear,
+            scene,
+            camera_matrix_text: String::new(),
+            camera_matrix_error: None,
+            camera_text: String::new(),
+            camera_text_error: None,
+            frame_stats_path: "frame_stats.csv".to_string(),
+            frame_stats_file: None,
+            frame_stats_error: None,
+            settings: cc
+                .storage
+                .and_then(|storage| eframe::get_value(storage, eframe::APP_KEY))
+                .unwrap_or_default(),
+            banner,
+            scene_path: scene_path
+                .map(|path| path.display().to_string())
+                .unwrap_or_else(|| "scene.json".to_string()),
+            selected_spheres: std::collections::BTreeSet::new(),
+            scene_dirty: false,
+            last_scene,
+            show_exit_confirm: false,
+            allowed_to_close: false,
+            tabs: vec![SceneTab::default()],
+            active_tab: 0,
+            scroll_to_selected: false,
+            dragged_sphere: None,
+            generate_options: Default::default(),
+            turntable: None,
+            on_fixed_update: None,
+            turntable_frames: 120,
+            compare_texture: None,
+            compare_split: 0.5,
+            split_camera: None,
+            camera_split: 0.5,
+            probe: None,
+            show_shader_source: false,
+            key_bindings: KeyBindings::default(),
+            show_key_help: false,
+            gpu_errors,
+            last_error: None,
+            undo_stack: vec![],
+            bulk_color: [1.0, 1.0, 1.0],
+            bulk_offset: cgmath::vec3(0.0, 0.0, 0.0),
+            export_path: "render".to_string(),
+            backplate_path: "backplate.png".to_string(),
+            sphere_csv_path: "spheres.csv".to_string(),
+            sphere_csv_watcher: None,
+            camera_file_path: "camera.json".to_string(),
+            camera_file_watcher: None,
+            camera_file: None,
+            camera_track_time: 0.0,
+            camera_track_frame: None,
+            shader_watcher: None,
+            orbit: None,
+            camera_tween: None,
+            camera_locked: false,
+            recenter_origin: cgmath::vec3(0.0, 0.0, 0.0),
+            #[cfg(feature = "gamepad")]
+            gamepad: gamepad::Gamepad::new(),
+        }
+    }
+
+    const FIXED_UPDATE_RATES: [u32; 3] = [30, 60, 120];
+    // movement is scaled by the frame time, so a long hitch would otherwise fling the camera across the scene
+    const MAX_FRAME_DELTA: std::time::Duration = std::time::Duration::from_millis(100);
+    // the source file rather than the copy baked in, so edits show up without rebuilding
+    const SHADER_PATH: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/src/shader.wgsl");
 
-        let pipeline =
+    fn render(&mut self, ts: f64, render_state: &egui_wgpu::RenderState, size: (usize, usize)) {
+        let start_frame_time = std::time::Instant::now();
+
+        let mut shader_reloaded = false;
+        if self
+            .shader_watcher
+            .as_ref()
+            .is_some_and(|watcher| watcher.changed())
+        {
+            let result = std::fs::read_to_string(Self::SHADER_PATH)
+                .map_err(|error| error.to_string())
+                .and_then(|source| self.renderer.reload_shader(&render_state.device, &source));
+            match result {
+                Ok(()) => {
+                    shader_reloaded = true;
+                    self.banner = None;
+                }
+                Err(error) => {
+                    self.banner = Some(format!("Failed to reload '{}': {error}", Self::SHADER_PATH))
+                }
+            }
+        }
+
+        let texture_filter = if self.settings.nearest_filter {
+            wgpu::FilterMode::Nearest
+        } else {
+            wgpu::FilterMode::Linear
+        };
+        if self.renderer.resize(&render_state.device, size)
+            || shader_reloaded
+            || texture_filter != self.texture_filter
+        {
+            let mut renderer = render_state.renderer.write();
+            renderer.free_texture(&self.texture_id);
+            self.texture_id = renderer.register_native_texture(
+                &render_state.device,
+                &self
+                    .renderer
+                    .texture()
+                    .create_view(&wgpu::TextureViewDescriptor::default()),
+                texture_filter,
+            );
+            self.texture_filter = texture_filter;
+        }
+
+        let submission_index = self.renderer.render(
+            &render_state.device,
+            &render_state.queue,
+            &self.scene,
+            self.render_options(),
+        );
+
+        if self.settings.max_fps {
+            // frames overlap on the gpu, so the time between renders is the closest thing to a
+            // render time, averaged so it doesn't jump around every frame
+            if let Some(last_render_start) = self.last_render_start.replace(start_frame_time) {
+                let interval = start_frame_time - last_render_start;
+                self.last_frame_update_duration =
+                    self.last_frame_update_duration.mul_f64(0.9) + interval.mul_f64(0.1);
+            }
+        } else {
+            // this is slow but its just so the timings are a bit more accurate
             render_state
                 .device
-                .create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
-                    label: Some("Pipeline"),
-                    layout: None,
-                    module: &shader,
-                    entry_point: "main",
+                .poll(wgpu::Maintain::WaitForSubmissionIndex(submission_index));
+
+            self.last_frame_update_duration = start_frame_time.elapsed();
+            self.last_render_start = None;
+        }
+
+        if self.settings.auto_exposure {
+            // middle grey, the measured frame already has about the current exposure applied
+            const TARGET_LUMINANCE: f32 = 0.18;
+            let camera = &mut self.scene.camera;
+            // measurements come back a few frames late, the exposure eases towards the latest one
+            // every frame in between
+            if let Some(luminance) = self
+                .renderer
+                .average_luminance(&render_state.device, &render_state.queue)
+            {
+                self.target_exposure =
+                    Some(TARGET_LUMINANCE * camera.exposure / luminance.max(0.0001));
+            }
+            if let Some(target_exposure) = self.target_exposure {
+                let blend = 1.0 - (-self.settings.exposure_adaptation_speed * ts as f32).exp();
+                camera.exposure += (target_exposure - camera.exposure) * blend;
+            }
+        } else {
+            self.target_exposure = None;
+        }
+    }
+
+    /// What the viewport renders with, from the settings and the editing state
+    fn render_options(&self) -> RenderOptions {
+        RenderOptions {
+            selected_spheres: self.selected_spheres.clone(),
+            stereo: self.settings.stereo,
+            tile_size: self
+                .settings
+                .tiled_dispatch
+                .then_some(self.settings.tile_size),
+            dither: self.settings.dither,
+            camera_relative: self.settings.camera_relative,
+            checkerboard: self.settings.checkerboard,
+            edge_aa_samples: if self.settings.edge_aa {
+                self.settings.edge_aa_samples
+            } else {
+                0
+            },
+            edge_aa_threshold: self.settings.edge_aa_threshold,
+            clip_margin: if self.settings.clip_overlay {
+                self.settings.clip_margin
+            } else {
+                0.0
+            },
+            false_color: self.settings.false_color,
+            backplate: self.settings.backplate,
+            ssaa: self.settings.ssaa,
+            grid_spacing: if self.settings.grid {
+                self.settings.grid_spacing
+            } else {
+                0.0
+            },
+            grid_color: self.settings.grid_color,
+            split_camera: self.split_camera.and_then(|index| {
+                // the active camera's entry is out of date while it is being moved
+                if index == self.scene.active_camera {
+                    Some(self.scene.camera)
+                } else {
+                    self.scene.cameras.get(index).map(|named| named.camera)
+                }
+            }),
+            split: self.camera_split,
+        }
+    }
+
+    /// The viewport's options without the overlays that only help while editing, for renders
+    /// that get saved
+    fn export_render_options(&self) -> RenderOptions {
+        RenderOptions {
+            selected_spheres: Default::default(),
+            // a fresh renderer has no previous frame for the other half of the pixels
+            checkerboard: false,
+            clip_margin: 0.0,
+            false_color: false,
+            grid_spacing: 0.0,
+            split_camera: None,
+            ..self.render_options()
+        }
+    }
+
+    fn start_frame_stats(&mut self) -> std::io::Result<()> {
+        let mut file = std::io::BufWriter::new(std::fs::File::create(&self.frame_stats_path)?);
+        writeln!(
+            file,
+            "frame_time_ms,render_time_ms,fixed_update_time_ms,sphere_count"
+        )?;
+        self.frame_stats_file = Some(file);
+        Ok(())
+    }
+
+    fn stop_frame_stats(&mut self) -> std::io::Result<()> {
+        if let Some(mut file) = self.frame_stats_file.take() {
+            file.flush()?;
+        }
+        Ok(())
+    }
+
+    fn write_frame_stats(&mut self, ts: f64) -> std::io::Result<()> {
+        if let Some(file) = &mut self.frame_stats_file {
+            writeln!(
+                file,
+                "{},{},{},{}",
+                ts * 1000.0,
+                self.last_frame_update_duration.as_secs_f64() * 1000.0,
+                self.last_fixed_update_duration.as_secs_f64() * 1000.0,
+                self.scene.spheres.len()
+            )?;
+        }
+        Ok(())
+    }
+
+    /// A plain text summary of the gpu, settings and scene, for pasting into bug reports
+    fn diagnostics(&self, device: &wgpu::Device) -> String {
+        let mut lines = vec![format!("Version: {}", env!("CARGO_PKG_VERSION"))];
+        for (name, value) in gpu_limits(&device.limits()) {
+            lines.push(format!("{name}: {value}"));
+        }
+        lines.push(format!("Features: {:?}", device.features()));
+
+        let (width, height) = self.renderer.texture_size();
+        let settings = &self.settings;
+        lines.extend([
+            format!("Resolution: {width}x{height}"),
+            format!(
+                "Native DPI: {}, Max Texture Size: {}",
+                settings.native_dpi, settings.max_texture_size
+            ),
+            format!(
+                "Checkerboard: {}, Edge AA: {} ({} samples), SSAA: {}x, Stereo: {}, Dither: {}",
+                settings.checkerboard,
+                settings.edge_aa,
+                settings.edge_aa_samples,
+                settings.ssaa,
+                settings.stereo,
+                settings.dither
+            ),
+            format!(
+                "Tiled Dispatch: {} ({}px), Camera Relative: {}, Max FPS: {}",
+                settings.tiled_dispatch,
+                settings.tile_size,
+                settings.camera_relative,
+                settings.max_fps
+            ),
+            format!(
+                "Render Time: {:.3}ms",
+                self.last_frame_update_duration.as_secs_f64() * 1000.0
+            ),
+            format!("Spheres: {}", self.scene.spheres.len()),
+            format!(
+                "Last Error: {}",
+                self.last_error.as_deref().unwrap_or("none")
+            ),
+        ]);
+        lines.join("\n")
+    }
+
+    fn gpu_info_ui(&self, ui: &mut egui::Ui, device: &wgpu::Device) {
+        if ui
+            .button("Copy Diagnostics")
+            .on_hover_text("Copy a summary of the GPU, settings and scene for a bug report")
+            .clicked()
+        {
+            let diagnostics = self.diagnostics(device);
+            ui.output_mut(|output| output.copied_text = diagnostics);
+        }
+
+        let device_limits = device.limits();
+        egui::Grid::new("GPU Limits").striped(true).show(ui, |ui| {
+            ui.strong("Limit");
+            ui.strong("Device");
+            ui.end_row();
+            for (name, value) in gpu_limits(&device_limits) {
+                ui.label(name);
+                ui.label(value.to_string());
+                ui.end_row();
+            }
+        });
+
+        ui.label("Features:");
+        let features = device.features();
+        if features.is_empty() {
+            ui.label("None");
+        } else {
+            // bitflags debug prints the set flags separated by " | "
+            for feature in format!("{features:?}").split(" | ") {
+                ui.monospace(feature);
+            }
+        }
+    }
+
+    fn stats_ui(&mut self, ui: &mut egui::Ui, ts: f64) {
+        ui.label(format!("FPS: {:.3}", 1.0 / ts));
+        ui.label(format!(
+            "Render time: {:.3}ms",
+            self.last_frame_update_duration.as_secs_f64() * 1000.0
+        ));
+        {
+            // a rough estimate, a primary and a shadow ray per shaded pixel, more on see through
+            // layers and the extra edge aa samples
+            const RAYS_PER_PIXEL: f64 = 2.0;
+            let (width, height) = self.renderer.texture_size();
+            let ssaa = self.settings.ssaa.max(1) as f64;
+            let mut pixels = (width * height) as f64 * ssaa * ssaa;
+            if self.settings.checkerboard {
+                // only half the pixels are shaded each frame
+                pixels *= 0.5;
+            }
+            let rays = pixels * RAYS_PER_PIXEL;
+            let render_time = self.last_frame_update_duration.as_secs_f64();
+            ui.label(format!(
+                "Rays per second: ~{:.2}M",
+                rays / render_time.max(f64::EPSILON) / 1_000_000.0
+            ));
+        }
+        ui.label(format!(
+            "Fixed update time: {:.3}ms",
+            self.last_fixed_update_duration.as_secs_f64() * 1000.0
+        ));
+        ui.horizontal(|ui| {
+            ui.label("Fixed Update Rate:");
+            for rate in Self::FIXED_UPDATE_RATES {
+                ui.selectable_value(
+                    &mut self.settings.fixed_update_rate,
+                    rate,
+                    format!("{rate}Hz"),
+                );
+            }
+        });
+
+        match self.scene.bounds() {
+            Some((min, max)) => ui.label(format!(
+                "Bounds: ({:.2}, {:.2}, {:.2}) to ({:.2}, {:.2}, {:.2})",
+                min.x, min.y, min.z, max.x, max.y, max.z
+            )),
+            None => ui.label("Bounds: empty scene"),
+        };
+
+        ui.horizontal(|ui| {
+            ui.label("Stats CSV:");
+            ui.add_enabled(
+                self.frame_stats_file.is_none(),
+                egui::TextEdit::singleline(&mut self.frame_stats_path),
+            );
+        });
+        if self.frame_stats_file.is_none() {
+            if ui.button("Start Recording Stats").clicked() {
+                self.frame_stats_error = self.start_frame_stats().err().map(|e| e.to_string());
+            }
+        } else if ui.button("Stop Recording Stats").clicked() {
+            self.frame_stats_error = self.stop_frame_stats().err().map(|e| e.to_string());
+        }
+        if let Some(error) = &self.frame_stats_error {
+            ui.colored_label(egui::Color32::RED, error);
+        }
+    }
+
+    fn scene_ui(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            ui.label("Path:");
+            ui.text_edit_singleline(&mut self.scene_path);
+        });
+        ui.horizontal(|ui| {
+            if ui.button("Load").clicked() {
+                match Scene::load(self.scene_path.as_ref()) {
+                    Ok(scene) => {
+                        self.scene = scene;
+                        self.sync_orbit();
+                        self.selected_spheres.clear();
+                        self.undo_stack.clear();
+                        self.mark_clean();
+                        self.banner = None;
+                    }
+                    Err(error) => {
+                        self.banner = Some(format!("Failed to load '{}': {error}", self.scene_path))
+                    }
+                }
+            }
+            if ui.button("Load In New Tab").clicked() {
+                match Scene::load(self.scene_path.as_ref()) {
+                    Ok(scene) => {
+                        self.open_tab(self.scene_path.clone(), scene);
+                        self.banner = None;
+                    }
+                    Err(error) => {
+                        self.banner = Some(format!("Failed to load '{}': {error}", self.scene_path))
+                    }
+                }
+            }
+            if ui.button("Import glTF").clicked() {
+                match import::import_gltf(self.scene_path.as_ref(), self.scene.camera) {
+                    Ok(scene) => {
+                        self.scene = scene;
+                        // so Save writes a scene next to the gltf instead of over it
+                        self.scene_path = std::path::Path::new(&self.scene_path)
+                            .with_extension("json")
+                            .to_string_lossy()
+                            .into_owned();
+                        self.sync_orbit();
+                        self.selected_spheres.clear();
+                        self.undo_stack.clear();
+                        self.banner = None;
+                    }
+                    Err(error) => {
+                        self.banner =
+                            Some(format!("Failed to import '{}': {error}", self.scene_path))
+                    }
+                }
+            }
+            if ui.button("Save").clicked() {
+                self.banner = self
+                    .scene
+                    .save(self.scene_path.as_ref())
+                    .err()
+                    .map(|error| format!("Failed to save '{}': {error}", self.scene_path));
+                if self.banner.is_none() {
+                    self.mark_clean();
+                }
+            }
+        });
+    }
+
+    /// Notices edits made since the last call by comparing against the scene from then
+    fn track_edits(&mut self) {
+        // auto exposure changes the exposure every frame, that isn't an edit
+        if self.settings.auto_exposure {
+            self.last_scene.camera.exposure = self.scene.camera.exposure;
+        }
+        if !self.last_scene.matches(&self.scene) {
+            self.scene_dirty = true;
+            self.last_scene = SceneSnapshot::new(&self.scene);
+        }
+    }
+
+    // for when the scene matches the file it came from or was saved to
+    fn mark_clean(&mut self) {
+        self.scene_dirty = false;
+        self.last_scene = SceneSnapshot::new(&self.scene);
+    }
+
+    /// The names of the tabs with unsaved changes
+    fn unsaved_scenes(&self) -> Vec<String> {
+        self.tabs
+            .iter()
+            .enumerate()
+            .filter_map(|(i, tab)| {
+                if i == self.active_tab {
+                    self.scene_dirty.then_some(&self.scene_path)
+                } else {
+                    tab.dirty.then_some(&tab.scene_path)
+                }
+            })
+            .map(|scene_path| scene_name(scene_path))
+            .collect()
+    }
+
+    fn exit_confirm_ui(&mut self, ctx: &egui::Context, frame: &mut eframe::Frame) {
+        egui::Window::new("Unsaved Changes")
+            .collapsible(false)
+            .resizable(false)
+            .anchor(egui::Align2::CENTER_CENTER, egui::vec2(0.0, 0.0))
+            .show(ctx, |ui| {
+                ui.label(format!(
+                    "Unsaved changes in {} will be lost.",
+                    self.unsaved_scenes().join(", ")
+                ));
+                ui.horizontal(|ui| {
+                    if ui.button("Quit Without Saving").clicked() {
+                        self.allowed_to_close = true;
+                        frame.close();
+                    }
+                    if ui.button("Cancel").clicked() {
+                        self.show_exit_confirm = false;
+                    }
                 });
+            });
+    }
 
-        let texture_bind_group =
-            render_state
-                .device
-                .create_bind_group(&wgpu::BindGroupDescriptor {
-                    label: Some("Texture bind group"),
-                    layout: &pipeline.get_bind_group_layout(0),
-                    entries: &[wgpu::BindGroupEntry {
-                        binding: 0,
-                        resource: wgpu::BindingResource::TextureView(
-                            &texture.create_view(&wgpu::TextureViewDescriptor::default()),
-                        ),
-                    }],
+    fn swap_tab(&mut self, index: usize) {
+        let tab = &mut self.tabs[index];
+        std::mem::swap(&mut self.scene_path, &mut tab.scene_path);
+        std::mem::swap(&mut self.scene, &mut tab.scene);
+        std::mem::swap(&mut self.selected_spheres, &mut tab.selected_spheres);
+        std::mem::swap(&mut self.undo_stack, &mut tab.undo_stack);
+        std::mem::swap(&mut self.scene_dirty, &mut tab.dirty);
+    }
+
+    fn switch_tab(&mut self, index: usize) {
+        if index == self.active_tab {
+            return;
+        }
+        // stores the active tab, then takes the new one out of its slot
+        self.track_edits();
+        self.swap_tab(self.active_tab);
+        self.swap_tab(index);
+        self.active_tab = index;
+        // the other tab's scene isn't an edit
+        self.last_scene = SceneSnapshot::new(&self.scene);
+        self.camera_tween = None;
+        self.dragged_sphere = None;
+        self.probe = None;
+        self.sync_orbit();
+    }
+
+    fn open_tab(&mut self, scene_path: String, scene: Scene) {
+        self.tabs.push(SceneTab {
+            scene_path,
+            scene,
+            ..Default::default()
+        });
+        self.switch_tab(self.tabs.len() - 1);
+    }
+
+    fn close_tab(&mut self, index: usize) {
+        if self.tabs.len() <= 1 {
+            return;
+        }
+        if index == self.active_tab {
+            self.switch_tab(if index == 0 { 1 } else { index - 1 });
+        }
+        self.tabs.remove(index);
+        if index < self.active_tab {
+            self.active_tab -= 1;
+        }
+    }
+
+    fn tabs_ui(&mut self, ui: &mut egui::Ui) {
+        let mut switch_to = None;
+        let mut close = None;
+        ui.horizontal(|ui| {
+            for (i, tab) in self.tabs.iter().enumerate() {
+                let (scene_path, dirty) = if i == self.active_tab {
+                    (&self.scene_path, self.scene_dirty)
+                } else {
+                    (&tab.scene_path, tab.dirty)
+                };
+                let mut name = scene_name(scene_path);
+                if dirty {
+                    name.push('*');
+                }
+                if ui
+                    .selectable_label(i == self.active_tab, name)
+                    .on_hover_text(scene_path)
+                    .clicked()
+                {
+                    switch_to = Some(i);
+                }
+                if self.tabs.len() > 1 && ui.small_button("x").clicked() {
+                    close = Some(i);
+                }
+                ui.separator();
+            }
+            if ui
+                .button("+")
+                .on_hover_text("Open an empty scene")
+                .clicked()
+            {
+                self.open_tab("scene.json".into(), Scene::default());
+            }
+        });
+        if let Some(index) = switch_to {
+            self.switch_tab(index);
+        }
+        if let Some(index) = close {
+            self.close_tab(index);
+        }
+    }
+
+    fn sphere_csv_ui(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            ui.label("Spheres CSV:");
+            ui.add_enabled(
+                self.sphere_csv_watcher.is_none(),
+                egui::TextEdit::singleline(&mut self.sphere_csv_path),
+            );
+        });
+        if self.sphere_csv_watcher.is_none() {
+            if ui
+                .button("Watch CSV")
+                .on_hover_text(
+                    "Replace the spheres with x,y,z,radius,r,g,b rows whenever the file changes",
+                )
+                .clicked()
+            {
+                match file_watcher::FileWatcher::new(self.sphere_csv_path.as_ref()) {
+                    Ok(watcher) => {
+                        self.sphere_csv_watcher = Some(watcher);
+                        self.reload_sphere_csv();
+                    }
+                    Err(error) => {
+                        self.banner = Some(format!(
+                            "Failed to watch '{}': {error}",
+                            self.sphere_csv_path
+                        ))
+                    }
+                }
+            }
+        } else if ui.button("Stop Watching CSV").clicked() {
+            self.sphere_csv_watcher = None;
+        }
+    }
+
+    // on failure the current spheres are kept
+    fn reload_sphere_csv(&mut self) {
+        let Some(watcher) = &self.sphere_csv_watcher else {
+            return;
+        };
+        match sphere_csv::load_spheres_csv(watcher.path()) {
+            Ok(spheres) => {
+                self.scene.spheres = spheres;
+                // the csv has no names, and the old ones belonged to whatever spheres were there before
+                self.scene.sphere_names.clear();
+                self.scene.sphere_ids.clear();
+                self.selected_spheres
+                    .retain(|&i| i < self.scene.spheres.len());
+                self.undo_stack.clear();
+                self.banner = None;
+            }
+            Err(error) => {
+                self.banner = Some(format!(
+                    "Failed to load '{}': {error}",
+                    watcher.path().display()
+                ))
+            }
+        }
+    }
+
+    fn camera_file_ui(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            ui.label("Camera JSON:");
+            ui.add_enabled(
+                self.camera_file_watcher.is_none(),
+                egui::TextEdit::singleline(&mut self.camera_file_path),
+            );
+        });
+        if self.camera_file_watcher.is_none() {
+            if ui
+                .button("Watch Camera")
+                .on_hover_text(
+                    "Set the camera from a camera object, or play an array of them as a track, whenever the file changes",
+                )
+                .clicked()
+            {
+                match file_watcher::FileWatcher::new(self.camera_file_path.as_ref()) {
+                    Ok(watcher) => {
+                        self.camera_file_watcher = Some(watcher);
+                        self.camera_file = None;
+                        self.camera_track_time = 0.0;
+                        self.camera_track_frame = None;
+                        self.reload_camera_file();
+                    }
+                    Err(error) => {
+                        self.banner = Some(format!(
+                            "Failed to watch '{}': {error}",
+                            self.camera_file_path
+                        ))
+                    }
+                }
+            }
+        } else {
+            let frame_count = self
+                .camera_file
+                .as_ref()
+                .map_or(0, camera_file::CameraFile::frame_count);
+            if frame_count > 1 {
+                ui.horizontal(|ui| {
+                    ui.label(format!(
+                        "Frame {} of {frame_count}",
+                        self.camera_track_frame.unwrap_or(0) + 1
+                    ));
+                    ui.label("FPS:");
+                    ui.add(
+                        egui::DragValue::new(&mut self.settings.camera_track_fps)
+                            .speed(0.1)
+                            .clamp_range(0.0..=240.0),
+                    );
                 });
+            }
+            if ui.button("Stop Watching Camera").clicked() {
+                self.camera_file_watcher = None;
+                self.camera_file = None;
+            }
+        }
+    }
 
-        let camera = Camera {
-            position: (0.0, 0.0, -3.0).into(),
-            rotation: Quaternion::from_axis_angle((0.0, 0.0, 1.0).into(), cgmath::Deg(0.0)),
-            up_sky_color: (1.0, 1.0, 1.0).into(),
-            down_sky_color: (0.5, 0.7, 1.0).into(),
-            min_distance: 0.001,
-            max_distance: 1000.0,
+    // on failure the last camera file that loaded keeps driving the camera
+    fn reload_camera_file(&mut self) {
+        let Some(watcher) = &self.camera_file_watcher else {
+            return;
         };
+        match camera_file::CameraFile::load(watcher.path()) {
+            Ok(camera_file) => {
+                self.camera_file = Some(camera_file);
+                // the frame may be the same one, but what it holds changed
+                self.camera_track_frame = None;
+                self.banner = None;
+            }
+            Err(error) => {
+                self.banner = Some(format!(
+                    "Failed to load '{}', keeping the last camera: {error}",
+                    watcher.path().display()
+                ))
+            }
+        }
+    }
 
-        let camera_buffer = {
-            let camera_uniform: CameraUniform = camera.into();
-            let mut buffer =
-                UniformBuffer::new([0u8; <CameraUniform as ShaderSize>::SHADER_SIZE.get() as _]);
-            buffer.write(&camera_uniform).unwrap();
-            render_state
-                .device
-                .create_buffer_init(&wgpu::util::BufferInitDescriptor {
-                    label: Some("Camera Buffer"),
-                    contents: &buffer.into_inner(),
-                    usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
-                })
+    fn update_camera_file(&mut self, ts: f64) {
+        let Some(camera_file) = &self.camera_file else {
+            return;
+        };
+        self.camera_track_time += ts;
+        let frame = (self.camera_track_time * self.settings.camera_track_fps as f64) as usize
+            % camera_file.frame_count();
+        if self.camera_track_frame == Some(frame) {
+            return;
+        }
+        camera_file.apply(frame, &mut self.scene.camera);
+        self.camera_track_frame = Some(frame);
+        self.camera_tween = None;
+        self.sync_orbit();
+    }
+
+    fn post_ui(&mut self, ui: &mut egui::Ui) {
+        let camera = &mut self.scene.camera;
+        ui.horizontal(|ui| {
+            ui.label("Exposure:");
+            ui.add_enabled(
+                !self.settings.auto_exposure,
+                egui::DragValue::new(&mut camera.exposure)
+                    .speed(0.01)
+                    .clamp_range(0.0..=f32::INFINITY),
+            );
+        });
+        ui.horizontal(|ui| {
+            ui.checkbox(&mut self.settings.auto_exposure, "Auto Exposure");
+            ui.label("Adaptation Speed:");
+            ui.add_enabled(
+                self.settings.auto_exposure,
+                egui::DragValue::new(&mut self.settings.exposure_adaptation_speed)
+                    .speed(0.01)
+                    .clamp_range(0.0..=100.0),
+            );
+        });
+        ui.checkbox(&mut self.settings.false_color, "False Color")
+            .on_hover_text(
+                "Show exposure instead of the image: blue is crushed, green is middle grey, \
+                 yellow to orange is close to clipping and red is clipped. Exports are unaffected",
+            );
+        ui.horizontal(|ui| {
+            ui.label("Fog Density:");
+            ui.add(
+                egui::DragValue::new(&mut camera.fog_density)
+                    .speed(0.001)
+                    .clamp_range(0.0..=f32::INFINITY),
+            );
+        });
+        ui.add_enabled_ui(camera.fog_density > 0.0, |ui| {
+            ui.horizontal(|ui| {
+                ui.label("Fog Color:");
+                let mut fog_color = camera.fog_color.into();
+                color_edit(ui, "Fog Color", &mut fog_color);
+                camera.fog_color = fog_color.into();
+            });
+            ui.checkbox(&mut camera.fog_sky, "Fog The Sky");
+        });
+        ui.checkbox(&mut camera.vignette, "Vignette");
+        ui.add_enabled_ui(camera.vignette, |ui| {
+            ui.horizontal(|ui| {
+                ui.label("Radius:");
+                ui.add(
+                    egui::DragValue::new(&mut camera.vignette_radius)
+                        .speed(0.01)
+                        .clamp_range(0.0..=1.0),
+                );
+            });
+            ui.horizontal(|ui| {
+                ui.label("Intensity:");
+                ui.add(
+                    egui::DragValue::new(&mut camera.vignette_intensity)
+                        .speed(0.01)
+                        .clamp_range(0.0..=1.0),
+                );
+            });
+        });
+    }
+
+    fn render_ui(&mut self, ui: &mut egui::Ui) {
+        let settings = &mut self.settings;
+        let edge_aa_samples = if settings.edge_aa {
+            settings.edge_aa_samples
+        } else {
+            0
+        };
+        let current_preset = QUALITY_PRESETS
+            .iter()
+            .find(
+                |&&(_, checkerboard, native_dpi, max_texture_size, ssaa, samples)| {
+                    checkerboard == settings.checkerboard
+                        && native_dpi == settings.native_dpi
+                        && max_texture_size == settings.max_texture_size
+                        && ssaa == settings.ssaa
+                        && samples == edge_aa_samples
+                },
+            )
+            .map_or("Custom", |&(name, ..)| name);
+        egui::ComboBox::from_label("Quality Preset")
+            .selected_text(current_preset)
+            .show_ui(ui, |ui| {
+                // picking "Custom" keeps whatever settings are already set
+                let _ = ui.selectable_label(current_preset == "Custom", "Custom");
+                for &(name, checkerboard, native_dpi, max_texture_size, ssaa, samples) in
+                    QUALITY_PRESETS
+                {
+                    if ui.selectable_label(current_preset == name, name).clicked() {
+                        settings.checkerboard = checkerboard;
+                        settings.native_dpi = native_dpi;
+                        settings.max_texture_size = max_texture_size;
+                        settings.ssaa = ssaa;
+                        settings.edge_aa = samples > 0;
+                        // turning edge aa off keeps the sample count for when it's turned back on
+                        if samples > 0 {
+                            settings.edge_aa_samples = samples;
+                        }
+                    }
+                }
+            });
+        ui.horizontal(|ui| {
+            ui.checkbox(
+                &mut self.settings.throttle_when_unfocused,
+                "Throttle When Unfocused",
+            );
+            ui.add_enabled(
+                self.settings.throttle_when_unfocused,
+                egui::DragValue::new(&mut self.settings.unfocused_fps)
+                    .suffix(" fps")
+                    .clamp_range(0.0..=60.0),
+            )
+            .on_hover_text("0 pauses rendering until the window is focused again");
+        });
+        ui.checkbox(&mut self.settings.dither, "Dither")
+            .on_hover_text("Break up banding in the sky gradient");
+        ui.checkbox(&mut self.settings.camera_relative, "Camera Relative")
+            .on_hover_text("Keep far away geometry precise by rendering around the camera");
+        ui.horizontal(|ui| {
+            if ui
+                .button("Recenter On Camera")
+                .on_hover_text(
+                    "Move the spheres and the camera together so the camera sits at this point, \
+                     the view stays the same and it can be undone",
+                )
+                .clicked()
+            {
+                self.recenter_on_camera(self.recenter_origin);
+            }
+            for (value, prefix) in [
+                (&mut self.recenter_origin.x, "x: "),
+                (&mut self.recenter_origin.y, "y: "),
+                (&mut self.recenter_origin.z, "z: "),
+            ] {
+                ui.add(egui::DragValue::new(value).prefix(prefix).speed(0.1));
+            }
+        });
+        ui.checkbox(&mut self.settings.max_fps, "Max FPS")
+            .on_hover_text("Let frames overlap on the GPU, the render time becomes an average");
+        ui.checkbox(&mut self.settings.checkerboard, "Checkerboard")
+            .on_hover_text(
+                "Render half the pixels each frame and reuse the rest from the last one",
+            );
+        let mut hot_reload = self.shader_watcher.is_some();
+        if ui
+            .checkbox(&mut hot_reload, "Hot Reload Shader")
+            .on_hover_text(Self::SHADER_PATH)
+            .changed()
+        {
+            self.shader_watcher = None;
+            if hot_reload {
+                match file_watcher::FileWatcher::new(Self::SHADER_PATH.as_ref()) {
+                    Ok(watcher) => self.shader_watcher = Some(watcher),
+                    Err(error) => {
+                        self.banner =
+                            Some(format!("Failed to watch '{}': {error}", Self::SHADER_PATH))
+                    }
+                }
+            }
+        }
+        ui.toggle_value(&mut self.show_shader_source, "Shader Source")
+            .on_hover_text("The WGSL the current pipeline was compiled from");
+        ui.horizontal(|ui| {
+            ui.label("Upscale Filter:");
+            ui.radio_value(&mut self.settings.nearest_filter, false, "Linear");
+            ui.radio_value(&mut self.settings.nearest_filter, true, "Nearest")
+                .on_hover_text("Keep pixels crisp when the render is smaller than the viewport");
+        });
+        ui.horizontal(|ui| {
+            ui.checkbox(&mut self.settings.native_dpi, "Render at Native DPI")
+                .on_hover_text(
+                    "Render a pixel for every screen pixel, sharper on high DPI screens",
+                );
+            ui.label("Max Texture Size:");
+            ui.add(
+                egui::DragValue::new(&mut self.settings.max_texture_size)
+                    .clamp_range(64..=16384)
+                    .suffix(" px"),
+            );
+        });
+        ui.horizontal(|ui| {
+            ui.checkbox(&mut self.settings.edge_aa, "Edge AA")
+                .on_hover_text("Render pixels on silhouette edges again with extra samples");
+            ui.add_enabled_ui(self.settings.edge_aa, |ui| {
+                ui.label("Samples:");
+                ui.add(
+                    egui::DragValue::new(&mut self.settings.edge_aa_samples).clamp_range(1..=64),
+                );
+                ui.label("Threshold:");
+                ui.add(
+                    egui::DragValue::new(&mut self.settings.edge_aa_threshold)
+                        .speed(0.001)
+                        .clamp_range(0.001..=1.0),
+                )
+                .on_hover_text(
+                    "How much the depth has to jump between neighbors, relative to the depth",
+                );
+            });
+        });
+        egui::ComboBox::from_label("SSAA")
+            .selected_text(format!("{}x", self.settings.ssaa))
+            .show_ui(ui, |ui| {
+                for factor in [1, 2, 4] {
+                    ui.selectable_value(&mut self.settings.ssaa, factor, format!("{factor}x"));
+                }
+            })
+            .response
+            .on_hover_text(
+                "Render at this many times the resolution along each axis and average it down",
+            );
+        ui.horizontal(|ui| {
+            ui.checkbox(&mut self.settings.clip_overlay, "Show Clipping")
+                .on_hover_text(
+                    "Tint hits close to the min distance blue and ones close to the max distance magenta",
+                );
+            ui.add_enabled_ui(self.settings.clip_overlay, |ui| {
+                ui.label("Margin:");
+                ui.add(
+                    egui::DragValue::new(&mut self.settings.clip_margin)
+                        .speed(0.01)
+                        .clamp_range(0.001..=f32::MAX),
+                );
+            });
+        });
+        ui.horizontal(|ui| {
+            ui.checkbox(&mut self.settings.grid, "Ground Grid")
+                .on_hover_text("Draw grid lines on the y = 0 plane, fading out with distance");
+            ui.add_enabled_ui(self.settings.grid, |ui| {
+                ui.label("Spacing:");
+                ui.add(
+                    egui::DragValue::new(&mut self.settings.grid_spacing)
+                        .speed(0.01)
+                        .clamp_range(0.01..=f32::MAX),
+                );
+                color_edit(ui, "Grid Color", &mut self.settings.grid_color);
+            });
+        });
+        ui.horizontal(|ui| {
+            ui.checkbox(&mut self.settings.tiled_dispatch, "Tiled Dispatch")
+                .on_hover_text(
+                    "Submit the frame in tiles so large renders don't trip the GPU timeout",
+                );
+            ui.label("Tile Size:");
+            ui.add_enabled(
+                self.settings.tiled_dispatch,
+                egui::DragValue::new(&mut self.settings.tile_size).clamp_range(16..=4096),
+            );
+        });
+    }
+
+    /// Puts every render and post setting back to its default, the spheres, camera placement,
+    /// bookmarks and panel layout are kept
+    fn reset_settings(&mut self) {
+        self.settings = Settings {
+            panel_on_right: self.settings.panel_on_right,
+            escape_to_exit: self.settings.escape_to_exit,
+            max_spheres: self.settings.max_spheres,
+            snap_to_grid: self.settings.snap_to_grid,
+            grid_size: self.settings.grid_size,
+            nudge_step: self.settings.nudge_step,
+            camera_bookmarks: self.settings.camera_bookmarks,
+            ..Default::default()
+        };
+        let camera = &mut self.scene.camera;
+        *camera = camera::Camera {
+            position: camera.position,
+            rotation: camera.rotation,
+            ..Default::default()
+        };
+    }
+
+    fn compare_ui(&mut self, ui: &mut egui::Ui, render_state: &egui_wgpu::RenderState) {
+        ui.horizontal(|ui| {
+            if ui
+                .button("Freeze A")
+                .on_hover_text("Keep the current render to compare against")
+                .clicked()
+            {
+                self.clear_compare(render_state);
+                let texture = self
+                    .renderer
+                    .snapshot_texture(&render_state.device, &render_state.queue);
+                let texture_id = render_state.renderer.write().register_native_texture(
+                    &render_state.device,
+                    &texture.create_view(&wgpu::TextureViewDescriptor::default()),
+                    wgpu::FilterMode::Linear,
+                );
+                self.compare_texture = Some((texture, texture_id));
+            }
+            if ui
+                .add_enabled(self.compare_texture.is_some(), egui::Button::new("Clear A"))
+                .clicked()
+            {
+                self.clear_compare(render_state);
+            }
+        });
+        ui.add_enabled(
+            self.compare_texture.is_some(),
+            egui::Slider::new(&mut self.compare_split, 0.0..=1.0).text("Split"),
+        );
+    }
+
+    fn backplate_ui(&mut self, ui: &mut egui::Ui, render_state: &egui_wgpu::RenderState) {
+        ui.horizontal(|ui| {
+            ui.label("PNG Path:");
+            ui.text_edit_singleline(&mut self.backplate_path);
+        });
+        ui.horizontal(|ui| {
+            if ui.button("Load").clicked() {
+                let path = std::path::Path::new(&self.backplate_path);
+                match import::load_png(path) {
+                    Ok((size, pixels)) => {
+                        self.renderer.set_backplate(
+                            &render_state.device,
+                            &render_state.queue,
+                            Some((size, &pixels)),
+                        );
+                        self.settings.backplate = true;
+                        self.banner = None;
+                    }
+                    Err(error) => {
+                        self.banner = Some(format!("Failed to load '{}': {error}", path.display()))
+                    }
+                }
+            }
+            if ui
+                .add_enabled(self.renderer.has_backplate(), egui::Button::new("Clear"))
+                .clicked()
+            {
+                self.renderer
+                    .set_backplate(&render_state.device, &render_state.queue, None);
+            }
+        });
+        ui.add_enabled(
+            self.renderer.has_backplate(),
+            egui::Checkbox::new(&mut self.settings.backplate, "Show Backplate"),
+        )
+        .on_hover_text(
+            "Show the image stretched over the view wherever rays miss, instead of the sky",
+        );
+    }
+
+    fn clear_compare(&mut self, render_state: &egui_wgpu::RenderState) {
+        if let Some((_, texture_id)) = self.compare_texture.take() {
+            render_state.renderer.write().free_texture(&texture_id);
+        }
+    }
+
+    /// Renders the viewport again without the editing overlays and waits for it, so exports only
+    /// contain the scene, the next frame brings the overlays back
+    fn render_for_export(&mut self, render_state: &egui_wgpu::RenderState) {
+        let submission_index = self.renderer.render(
+            &render_state.device,
+            &render_state.queue,
+            &self.scene,
+            self.export_render_options(),
+        );
+        render_state
+            .device
+            .poll(wgpu::Maintain::WaitForSubmissionIndex(submission_index));
+    }
+
+    fn export_ui(&mut self, ui: &mut egui::Ui, render_state: &egui_wgpu::RenderState) {
+        ui.horizontal(|ui| {
+            ui.label("Path:");
+            ui.text_edit_singleline(&mut self.export_path);
+        });
+        ui.horizontal(|ui| {
+            ui.label("PNG Color Space:");
+            ui.radio_value(
+                &mut self.settings.png_color_space,
+                export::PngColorSpace::Srgb,
+                "sRGB",
+            );
+            ui.radio_value(
+                &mut self.settings.png_color_space,
+                export::PngColorSpace::Linear,
+                "Linear",
+            );
+        });
+        ui.horizontal(|ui| {
+            let path = std::path::PathBuf::from(&self.export_path);
+            let size = self.renderer.texture_size();
+            if ui
+                .add_enabled(
+                    self.renderer.hdr_texture_rendered(),
+                    egui::Button::new("Export PNG"),
+                )
+                .clicked()
+            {
+                self.render_for_export(render_state);
+                let pixels = self
+                    .renderer
+                    .read_hdr_texture(&render_state.device, &render_state.queue);
+                self.banner = export::save_png(
+                    &path.with_extension("png"),
+                    size,
+                    &pixels,
+                    self.settings.png_color_space,
+                )
+                .err();
+            }
+            if ui.button("Dump PPM").clicked() {
+                self.render_for_export(render_state);
+                let pixels = self
+                    .renderer
+                    .read_texture(&render_state.device, &render_state.queue);
+                self.banner = export::dump_ppm(&path.with_extension("ppm"), size, &pixels).err();
+            }
+            if ui
+                .add_enabled(
+                    self.renderer.hdr_texture_rendered(),
+                    egui::Button::new("Export EXR"),
+                )
+                .clicked()
+            {
+                self.render_for_export(render_state);
+                let pixels = self
+                    .renderer
+                    .read_hdr_texture(&render_state.device, &render_state.queue);
+                self.banner = export::save_exr(&path.with_extension("exr"), size, &pixels).err();
+            }
+        });
+        let aov_path = |name, extension| {
+            let mut path = std::path::Path::new(&self.export_path).with_extension("");
+            path.as_mut_os_string().push(format!("_{name}.{extension}"));
+            path
+        };
+        let size = self.renderer.texture_size();
+        let enabled = self.renderer.hdr_texture_rendered();
+        ui.horizontal(|ui| {
+            if ui
+                .add_enabled(enabled, egui::Button::new("Export Depth PNG"))
+                .on_hover_text("16 bit, black is the camera and white is the max distance")
+                .clicked()
+            {
+                let depths = self
+                    .renderer
+                    .read_depth_texture(&render_state.device, &render_state.queue);
+                self.banner = export::save_depth_png(
+                    &aov_path("depth", "png"),
+                    size,
+                    &depths,
+                    self.scene.camera.max_distance,
+                )
+                .err();
+            }
+            if ui
+                .add_enabled(enabled, egui::Button::new("Export Depth EXR"))
+                .on_hover_text("The distance along each pixel's ray in world units")
+                .clicked()
+            {
+                let depths = self
+                    .renderer
+                    .read_depth_texture(&render_state.device, &render_state.queue);
+                self.banner =
+                    export::save_depth_exr(&aov_path("depth", "exr"), size, &depths).err();
+            }
+        });
+        ui.horizontal(|ui| {
+            if ui
+                .add_enabled(enabled, egui::Button::new("Export Normals PNG"))
+                .on_hover_text("Remapped to 0..1 like a normal map, the sky is transparent")
+                .clicked()
+            {
+                let normals = self
+                    .renderer
+                    .read_normal_texture(&render_state.device, &render_state.queue);
+                self.banner =
+                    export::save_normal_png(&aov_path("normals", "png"), size, &normals).err();
+            }
+            if ui
+                .add_enabled(enabled, egui::Button::new("Export Normals EXR"))
+                .on_hover_text("World space normals as-is, alpha is 1 wherever something was hit")
+                .clicked()
+            {
+                let normals = self
+                    .renderer
+                    .read_normal_texture(&render_state.device, &render_state.queue);
+                self.banner = export::save_exr(&aov_path("normals", "exr"), size, &normals).err();
+            }
+        });
+        ui.horizontal(|ui| {
+            if ui
+                .button("Export OBJ")
+                .on_hover_text("Every sphere as a mesh with its own material, for other 3D tools")
+                .clicked()
+            {
+                self.banner = obj::save_obj(
+                    &std::path::Path::new(&self.export_path).with_extension("obj"),
+                    &self.scene.spheres,
+                    &obj::ObjOptions {
+                        subdivisions: self.settings.obj_subdivisions,
+                        merge: self.settings.obj_merge,
+                    },
+                )
+                .err();
+            }
+            ui.label("Subdivisions:");
+            ui.add(egui::DragValue::new(&mut self.settings.obj_subdivisions).clamp_range(2..=256));
+            ui.checkbox(&mut self.settings.obj_merge, "Merge Into One Object");
+        });
+        ui.horizontal(|ui| {
+            match &self.turntable {
+                Some(turntable) => {
+                    ui.add(egui::ProgressBar::new(turntable.progress()).show_percentage());
+                    if ui.button("Cancel").clicked() {
+                        self.turntable = None;
+                    }
+                }
+                None => {
+                    ui.label("Frames:");
+                    ui.add(egui::DragValue::new(&mut self.turntable_frames).clamp_range(1..=3600));
+                    if ui
+                        .button("Render Turntable")
+                        .on_hover_text("Orbit the scene and save every frame as a numbered PNG")
+                        .clicked()
+                    {
+                        self.turntable = Some(turntable::Turntable::new(
+                            &render_state.device,
+                            &self.scene,
+                            &std::path::Path::new(&self.export_path).with_extension(""),
+                            self.renderer.texture_size(),
+                            self.turntable_frames,
+                            self.export_render_options(),
+                        ));
+                    }
+                }
+            };
+        });
+    }
+
+    // the distance to the middle of the scene, so orbiting goes around what is being looked at
+    fn orbit_radius(&self) -> f32 {
+        self.scene
+            .bounds()
+            .map_or(3.0, |(min, max)| {
+                (self.scene.camera.position - (min + max) * 0.5).magnitude()
+            })
+            .max(0.1)
+    }
+
+    // call after the camera gets replaced so orbiting continues from where it is now
+    fn sync_orbit(&mut self) {
+        if self.orbit.is_some() {
+            self.orbit = Some(camera::Orbit::from_camera(
+                &self.scene.camera,
+                self.orbit_radius(),
+            ));
+        }
+    }
+
+    fn orbit_ui(&mut self, ui: &mut egui::Ui) {
+        let mut orbiting = self.orbit.is_some();
+        if ui.checkbox(&mut orbiting, "Orbit").changed() {
+            self.orbit = orbiting
+                .then(|| camera::Orbit::from_camera(&self.scene.camera, self.orbit_radius()));
+        }
+        let Some(orbit) = &mut self.orbit else {
+            return;
         };
+        ui.horizontal(|ui| {
+            ui.label("Target:");
+            ui.add(
+                egui::DragValue::new(&mut orbit.target.x)
+                    .prefix("x: ")
+                    .speed(0.1),
+            );
+            ui.add(
+                egui::DragValue::new(&mut orbit.target.y)
+                    .prefix("y: ")
+                    .speed(0.1),
+            );
+            ui.add(
+                egui::DragValue::new(&mut orbit.target.z)
+                    .prefix("z: ")
+                    .speed(0.1),
+            );
+        });
+        ui.horizontal(|ui| {
+            ui.label("Radius:");
+            ui.add(egui::DragValue::new(&mut orbit.radius).speed(0.1));
+            orbit.radius = orbit.radius.max(0.01);
+        });
+        let (mut azimuth, mut elevation) = orbit.angles();
+        let mut changed = false;
+        ui.horizontal(|ui| {
+            ui.label("Azimuth:");
+            changed |= ui
+                .add(egui::DragValue::new(&mut azimuth.0).suffix("°"))
+                .changed();
+        });
+        ui.horizontal(|ui| {
+            ui.label("Elevation:");
+            changed |= ui
+                .add(
+                    egui::DragValue::new(&mut elevation.0)
+                        .suffix("°")
+                        .clamp_range(-89.0..=89.0),
+                )
+                .changed();
+        });
+        if changed {
+            orbit.set_angles(azimuth, elevation);
+        }
+        ui.label("Right drag in the viewport to rotate around the target");
+    }
+
+    // dims the whole window, any key closes it
+    fn key_help_ui(ctx: &egui::Context, key_bindings: &KeyBindings) {
+        ctx.layer_painter(egui::LayerId::new(
+            egui::Order::Foreground,
+            egui::Id::new("Key Help Dim"),
+        ))
+        .rect_filled(ctx.screen_rect(), 0.0, egui::Color32::from_black_alpha(192));
+        egui::Area::new("Key Help")
+            .order(egui::Order::Tooltip)
+            .anchor(egui::Align2::CENTER_CENTER, egui::vec2(0.0, 0.0))
+            .show(ctx, |ui| {
+                egui::Frame::popup(ui.style()).show(ui, |ui| {
+                    ui.heading("Controls");
+                    egui::Grid::new("Key Help Grid").show(ui, |ui| {
+                        let is_mac = ctx.os() == egui::os::OperatingSystem::Mac;
+                        for (keys, action) in key_bindings.help(is_mac) {
+                            ui.strong(keys);
+                            ui.label(action);
+                            ui.end_row();
+                        }
+                    });
+                    ui.label("Press any key to close");
+                });
+            });
+    }
+
+    fn save_bookmark(&mut self, index: usize) {
+        self.settings.camera_bookmarks[index] = Some(self.scene.camera);
+    }
+
+    fn go_to_bookmark(&mut self, index: usize) {
+        if let Some(bookmark) = self.settings.camera_bookmarks[index] {
+            self.camera_tween = Some(camera::CameraTween::new(
+                self.scene.camera,
+                bookmark,
+                self.settings.bookmark_tween_duration,
+            ));
+        }
+    }
+
+    fn bookmarks_ui(&mut self, ui: &mut egui::Ui) {
+        ui.label("Press 1-8 to go to a bookmark, Shift+1-8 to save one");
+        ui.horizontal(|ui| {
+            ui.label("Tween Duration:");
+            ui.add(
+                egui::DragValue::new(&mut self.settings.bookmark_tween_duration)
+                    .suffix("s")
+                    .speed(0.05)
+                    .clamp_range(0.0..=10.0),
+            );
+        });
+        for index in 0..self.settings.camera_bookmarks.len() {
+            ui.horizontal(|ui| {
+                ui.label(format!("{}:", index + 1));
+                if ui.button("Save").clicked() {
+                    self.save_bookmark(index);
+                }
+                if ui
+                    .add_enabled(
+                        self.settings.camera_bookmarks[index].is_some(),
+                        egui::Button::new("Go"),
+                    )
+                    .clicked()
+                {
+                    self.go_to_bookmark(index);
+                }
+            });
+        }
+    }
+
+    fn switch_camera(&mut self, index: usize) {
+        self.scene.switch_camera(index);
+        self.camera_tween = None;
+        self.sync_orbit();
+    }
+
+    fn cameras_ui(&mut self, ui: &mut egui::Ui) {
+        let active_camera = self.scene.active_camera;
+        egui::ComboBox::from_label("Active Camera")
+            .selected_text(&self.scene.cameras[active_camera].name)
+            .show_ui(ui, |ui| {
+                for index in 0..self.scene.cameras.len() {
+                    let name = self.scene.cameras[index].name.clone();
+                    if ui.selectable_label(index == active_camera, name).clicked() {
+                        self.switch_camera(index);
+                    }
+                }
+            });
+        ui.horizontal(|ui| {
+            ui.label("Name:");
+            let active_camera = self.scene.active_camera;
+            ui.text_edit_singleline(&mut self.scene.cameras[active_camera].name);
+        });
+        ui.horizontal(|ui| {
+            if ui.button("Add Camera").clicked() {
+                // the new camera starts as a copy of the current one
+                self.scene.cameras.push(scene::NamedCamera {
+                    name: format!("Camera {}", self.scene.cameras.len() + 1),
+                    camera: self.scene.camera,
+                });
+                self.switch_camera(self.scene.cameras.len() - 1);
+            }
+            if ui
+                .add_enabled(
+                    self.scene.cameras.len() > 1,
+                    egui::Button::new("Remove Camera"),
+                )
+                .clicked()
+            {
+                self.scene.cameras.remove(self.scene.active_camera);
+                self.scene.active_camera =
+                    self.scene.active_camera.min(self.scene.cameras.len() - 1);
+                self.scene.camera = self.scene.cameras[self.scene.active_camera].camera;
+                self.camera_tween = None;
+                self.sync_orbit();
+            }
+        });
+        ui.horizontal(|ui| {
+            let mut split_screen = self.split_camera.is_some();
+            if ui
+                .checkbox(&mut split_screen, "Split Screen")
+                .on_hover_text(
+                    "Show another camera right of a line that can be dragged across the view",
+                )
+                .changed()
+            {
+                self.split_camera = split_screen.then_some(0);
+            }
+            if let Some(split_camera) = &mut self.split_camera {
+                // removing cameras can leave it pointing past the end
+                *split_camera = (*split_camera).min(self.scene.cameras.len() - 1);
+                egui::ComboBox::from_id_source("Split Camera")
+                    .selected_text(&self.scene.cameras[*split_camera].name)
+                    .show_ui(ui, |ui| {
+                        for (index, named) in self.scene.cameras.iter().enumerate() {
+                            ui.selectable_value(split_camera, index, &named.name);
+                        }
+                    });
+            }
+        });
+    }
+
+    #[cfg(feature = "gamepad")]
+    fn gamepad_ui(&mut self, ui: &mut egui::Ui) {
+        match self.gamepad.name() {
+            Some(name) => ui.label(format!("Connected: {name}")),
+            None => ui.weak("No controller connected"),
+        };
+        ui.horizontal(|ui| {
+            ui.label("Deadzone:");
+            ui.add(egui::Slider::new(
+                &mut self.settings.gamepad_deadzone,
+                0.0..=0.9,
+            ));
+        });
+        ui.horizontal(|ui| {
+            ui.label("Look Speed:");
+            ui.add(
+                egui::DragValue::new(&mut self.settings.gamepad_look_speed)
+                    .clamp_range(0.0..=1000.0)
+                    .suffix("°/s"),
+            );
+            ui.label("Move Speed:");
+            ui.add(
+                egui::DragValue::new(&mut self.settings.gamepad_move_speed)
+                    .speed(0.1)
+                    .clamp_range(0.0..=f32::INFINITY),
+            );
+        });
+    }
+
+    fn camera_ui(&mut self, ui: &mut egui::Ui) {
+        ui.checkbox(&mut self.camera_locked, "Lock Camera")
+            .on_hover_text("Ignore mouse and keyboard camera controls, even without focus");
+        #[cfg(feature = "gamepad")]
+        ui.collapsing("Gamepad", |ui| self.gamepad_ui(ui));
+        self.cameras_ui(ui);
+        self.orbit_ui(ui);
+        ui.collapsing("Bookmarks", |ui| self.bookmarks_ui(ui));
+
+        let bounds = self.scene.bounds();
+        if ui
+            .add_enabled(bounds.is_some(), egui::Button::new("Frame All"))
+            .clicked()
+        {
+            if let Some(bounds) = bounds {
+                let (width, height) = self.renderer.texture_size();
+                // each eye gets half the width
+                let width = if self.settings.stereo {
+                    width / 2
+                } else {
+                    width
+                };
+                self.scene
+                    .camera
+                    .frame_bounds(bounds, width as f32 / height.max(1) as f32);
+                self.sync_orbit();
+            }
+        }
+
+        let up_sky_color: [f32; 3] = self.scene.camera.up_sky_color.into();
+        let down_sky_color: [f32; 3] = self.scene.camera.down_sky_color.into();
+        let current_preset = SKY_PRESETS
+            .iter()
+            .find(|&&(_, up, down)| up == up_sky_color && down == down_sky_color)
+            .map_or("Custom", |&(name, _, _)| name);
+        egui::ComboBox::from_label("Sky Preset")
+            .selected_text(current_preset)
+            .show_ui(ui, |ui| {
+                // picking "Custom" keeps whatever colors are already set
+                let _ = ui.selectable_label(current_preset == "Custom", "Custom");
+                for &(name, up, down) in SKY_PRESETS {
+                    if ui.selectable_label(current_preset == name, name).clicked() {
+                        self.scene.camera.up_sky_color = up.into();
+                        self.scene.camera.down_sky_color = down.into();
+                    }
+                }
+            });
+        ui.horizontal(|ui| {
+            ui.label("Up Sky Color:");
+            let mut up_sky_color = self.scene.camera.up_sky_color.into();
+            color_edit(ui, "Up Sky Color", &mut up_sky_color);
+            self.scene.camera.up_sky_color = up_sky_color.into();
+        });
+        ui.horizontal(|ui| {
+            ui.label("Down Sky Color:");
+            let mut down_sky_color = self.scene.camera.down_sky_color.into();
+            color_edit(ui, "Down Sky Color", &mut down_sky_color);
+            self.scene.camera.down_sky_color = down_sky_color.into();
+        });
+        ui.horizontal(|ui| {
+            ui.label("Sky Intensity:");
+            ui.add(
+                egui::DragValue::new(&mut self.scene.camera.sky_intensity)
+                    .speed(0.01)
+                    .clamp_range(0.0..=f32::INFINITY),
+            );
+        });
+        if !self.scene.camera.sky_is_finite() {
+            ui.colored_label(
+                egui::Color32::YELLOW,
+                "The sky intensity is too high, the sky is no longer finite",
+            );
+        }
+
+        ui.horizontal(|ui| {
+            ui.label("Min Distance:");
+            ui.add(egui::DragValue::new(&mut self.scene.camera.min_distance).speed(0.001));
+            self.scene.camera.min_distance = self.scene.camera.min_distance.max(0.0001);
+        });
+        ui.horizontal(|ui| {
+            ui.label("Max Distance:");
+            ui.add(egui::DragValue::new(&mut self.scene.camera.max_distance).speed(1.0));
+            self.scene.camera.max_distance = self.scene.camera.max_distance.max(0.0);
+        });
+        ui.horizontal(|ui| {
+            ui.label("Shadow Bias:");
+            ui.add(egui::DragValue::new(&mut self.scene.camera.shadow_bias).speed(0.0001));
+            self.scene.camera.shadow_bias = self.scene.camera.shadow_bias.max(0.0);
+        });
+        ui.horizontal(|ui| {
+            ui.checkbox(&mut self.settings.stereo, "Stereo");
+            ui.label("Eye Separation:");
+            ui.add(egui::DragValue::new(&mut self.scene.camera.eye_separation).speed(0.001));
+        });
+        ui.horizontal(|ui| {
+            ui.label("Pixel Aspect:");
+            ui.add(
+                egui::DragValue::new(&mut self.scene.camera.pixel_aspect)
+                    .speed(0.01)
+                    .clamp_range(0.1..=10.0),
+            )
+            .on_hover_text("The width of a pixel over its height, for anamorphic output");
+        });
 
-        let camera_bind_group = render_state
-            .device
-            .create_bind_group(&wgpu::BindGroupDescriptor {
-                layout: &pipeline.get_bind_group_layout(1),
-                entries: &[wgpu::BindGroupEntry {
-                    binding: 0,
-                    resource: camera_buffer.as_entire_binding(),
-                }],
-                label: Some("camera_bind_group"),
+        ui.collapsing("Matrix", |ui| {
+            let matrix = self.scene.camera.world_matrix();
+            egui::Grid::new("Camera Matrix").show(ui, |ui| {
+                for row in 0..4 {
+                    for column in 0..4 {
+                        ui.label(format!("{:.3}", matrix[column][row]));
+                    }
+                    ui.end_row();
+                }
             });
+            if ui.button("Copy Matrix").clicked() {
+                self.camera_matrix_text = (0..4)
+                    .map(|row| {
+                        (0..4)
+                            .map(|column| matrix[column][row].to_string())
+                            .collect::<Vec<_>>()
+                            .join(" ")
+                    })
+                    .collect::<Vec<_>>()
+                    .join("\n");
+                ui.output_mut(|output| output.copied_text = self.camera_matrix_text.clone());
+            }
+            ui.text_edit_multiline(&mut self.camera_matrix_text);
+            if ui.button("Apply Matrix").clicked() {
+                match parse_matrix(&self.camera_matrix_text)
+                    .and_then(|matrix| self.scene.camera.set_world_matrix(matrix))
+                {
+                    Ok(()) => {
+                        self.sync_orbit();
+                        self.camera_matrix_error = None;
+                    }
+                    Err(error) => self.camera_matrix_error = Some(error),
+                }
+            }
+            if let Some(error) = &self.camera_matrix_error {
+                ui.colored_label(egui::Color32::RED, error);
+            }
+        });
 
-        let spheres_storage = SpheresBuffer {
-            sphere_count: ArrayLength::default(),
-            spheres: vec![Sphere::default()],
-        };
+        // the same json as the camera in a saved scene, so either can be pasted into the other
+        ui.collapsing("Share", |ui| {
+            if ui.button("Copy Camera").clicked() {
+                self.camera_text = serde_json::to_string(&self.scene.camera).unwrap();
+                ui.output_mut(|output| output.copied_text = self.camera_text.clone());
+            }
+            ui.text_edit_singleline(&mut self.camera_text);
+            if ui.button("Paste Camera").clicked() {
+                match serde_json::from_str(&self.camera_text) {
+                    Ok(camera) => {
+                        self.scene.camera = camera;
+                        self.sync_orbit();
+                        self.camera_text_error = None;
+                    }
+                    Err(error) => self.camera_text_error = Some(error.to_string()),
+                }
+            }
+            if let Some(error) = &self.camera_text_error {
+                ui.colored_label(egui::Color32::RED, error);
+            }
+        });
+    }
 
-        let (spheres_buffer, spheres_buffer_size) = {
-            let mut buffer =
-                StorageBuffer::new(Vec::with_capacity(spheres_storage.size().get() as _));
-            buffer.write(&spheres_storage).unwrap();
-            let buffer = buffer.into_inner();
-            (
-                render_state
-                    .device
-                    .create_buffer_init(&wgpu::util::BufferInitDescriptor {
-                        label: Some("Sphere Buffer"),
-                        contents: &buffer,
-                        usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
-                    }),
-                buffer.len(),
+    fn spheres_ui(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            ui.label("Max Spheres:");
+            ui.add(egui::DragValue::new(&mut self.settings.max_spheres));
+        });
+        ui.checkbox(
+            &mut self.settings.show_sphere_labels,
+            "Show Labels In Viewport",
+        );
+        ui.horizontal(|ui| {
+            ui.checkbox(&mut self.settings.snap_to_grid, "Snap");
+            ui.label("Grid Size:");
+            ui.add(egui::DragValue::new(&mut self.settings.grid_size).speed(0.01));
+            self.settings.grid_size = self.settings.grid_size.max(0.001);
+        });
+        ui.horizontal(|ui| {
+            ui.label("Nudge Step:");
+            ui.add(egui::DragValue::new(&mut self.settings.nudge_step).speed(0.01))
+                .on_hover_text("How far Alt + Arrow Keys move the selected spheres");
+            self.settings.nudge_step = self.settings.nudge_step.max(0.001);
+        });
+        if ui
+            .add_enabled(
+                self.scene.spheres.len() < self.settings.max_spheres,
+                egui::Button::new("Add Sphere"),
             )
-        };
-
-        let spheres_bind_group =
-            render_state
-                .device
-                .create_bind_group(&wgpu::BindGroupDescriptor {
-                    layout: &pipeline.get_bind_group_layout(2),
-                    entries: &[wgpu::BindGroupEntry {
-                        binding: 0,
-                        resource: spheres_buffer.as_entire_binding(),
-                    }],
-                    label: Some("spheres_bind_group"),
-                });
-
-        Self {
-            last_frame_time: std::time::Instant::now(),
-            fixed_update_time: 0.0,
-            last_frame_update_duration: std::time::Duration::ZERO,
-            last_fixed_update_duration: std::time::Duration::ZERO,
-            texture_size: (width, height),
-            texture_bind_group,
-            texture_id,
-            pipeline,
-            camera,
-            camera_buffer,
-            camera_bind_group,
-            spheres_storage,
-            spheres_buffer,
-            spheres_bind_group,
-            spheres_buffer_size,
+            .clicked()
+        {
+            self.scene.spheres.push(Sphere::default());
+        }
+        if ui
+            .add_enabled(!self.undo_stack.is_empty(), egui::Button::new("Undo"))
+            .clicked()
+            || (!ui.ctx().wants_keyboard_input()
+                && ui.input_mut(|i| i.consume_shortcut(&self.key_bindings.undo)))
+        {
+            self.undo();
+        }
+        ui.collapsing("Generate", |ui| self.generate_ui(ui));
+        if !self.selected_spheres.is_empty() {
+            ui.collapsing(
+                format!("Edit {} Selected", self.selected_spheres.len()),
+                |ui| self.bulk_edit_ui(ui),
+            );
         }
+        let grid_size = self
+            .settings
+            .snap_to_grid
+            .then_some(self.settings.grid_size);
+        // so every sphere has a name to edit
+        self.scene
+            .sphere_names
+            .resize(self.scene.spheres.len(), String::new());
+        self.scene.sync_sphere_ids();
+        egui::ScrollArea::new([false, true]).show(ui, |ui| {
+            let mut header_rects = vec![];
+            let mut i = 0;
+            while i < self.scene.spheres.len() {
+                let label = self.scene.sphere_label(i);
+                let sphere = &mut self.scene.spheres[i];
+                let name = &mut self.scene.sphere_names[i];
+                let id = self.scene.sphere_ids[i];
+                let mut to_remove = false;
+                let (_, header, _) =
+                    egui::collapsing_header::CollapsingState::load_with_default_open(
+                        ui.ctx(),
+                        ui.make_persistent_id(("Sphere Header", id)),
+                        false,
+                    )
+                    .show_header(ui, |ui| {
+                        if ui
+                            .add(egui::Label::new("☰").sense(egui::Sense::drag()))
+                            .on_hover_text("Drag to reorder")
+                            .drag_started()
+                        {
+                            self.dragged_sphere = Some(i);
+                        }
+                        let selected = self.selected_spheres.contains(&i);
+                        let response = ui.selectable_label(selected, label);
+                        if response.clicked() {
+                            let toggle = ui.input(|input| input.modifiers.command);
+                            Self::click_select(&mut self.selected_spheres, Some(i), toggle);
+                        }
+                        if selected && self.scroll_to_selected {
+                            response.scroll_to_me(Some(egui::Align::Center));
+                            self.scroll_to_selected = false;
+                        }
+                    })
+                    .body(|ui| {
+                        ui.horizontal(|ui| {
+                            ui.label("Name:");
+                            ui.add(
+                                egui::TextEdit::singleline(name).hint_text(format!("Sphere {i}")),
+                            );
+                        });
+                        sphere.edit_ui(ui, ui.make_persistent_id(("Sphere", id)), grid_size);
+                        if ui.button("Delete").clicked() {
+                            to_remove = true;
+                        }
+                    });
+                if to_remove {
+                    self.scene.spheres.remove(i as _);
+                    self.scene.sphere_names.remove(i);
+                    self.scene.sphere_ids.remove(i);
+                    self.selected_spheres = self
+                        .selected_spheres
+                        .iter()
+                        .filter(|&&selected| selected != i)
+                        .map(|&selected| if selected > i { selected - 1 } else { selected })
+                        .collect();
+                    // the header rects no longer line up with the spheres
+                    self.dragged_sphere = None;
+                } else {
+                    header_rects.push(header.response.rect);
+                    i += 1;
+                }
+            }
+            if let Some(from) = self.dragged_sphere {
+                // the index the dragged sphere would be inserted before
+                let to = ui.input(|i| i.pointer.interact_pos()).map(|pos| {
+                    header_rects
+                        .iter()
+                        .filter(|rect| rect.center().y < pos.y)
+                        .count()
+                });
+                if let Some(to) = to {
+                    let y = header_rects
+                        .get(to)
+                        .map_or_else(|| ui.min_rect().bottom(), |rect| rect.top());
+                    ui.painter()
+                        .hline(ui.min_rect().x_range(), y, ui.visuals().selection.stroke);
+                }
+                if ui.input(|i| i.pointer.any_released()) {
+                    self.dragged_sphere = None;
+                    if let Some(to) = to {
+                        self.move_sphere(from, to);
+                    }
+                }
+            }
+            ui.allocate_space(ui.available_size());
+        });
     }
 
-    const FIXED_UPDATE_TIMESTEP: f64 = 1.0 / 60.0;
-
-    fn render(
-        &mut self,
-        _ts: f64,
-        render_state: &egui_wgpu::RenderState,
-        size @ (width, height): (usize, usize),
+    // a plain click selects just `clicked` or deselects it if it was the only selection,
+    // `toggle` adds or removes it from the selection instead
+    fn click_select(
+        selected_spheres: &mut std::collections::BTreeSet<usize>,
+        clicked: Option<usize>,
+        toggle: bool,
     ) {
-        let start_frame_time = std::time::Instant::now();
-
-        if self.texture_size != size && width != 0 && height != 0 {
-            let mut renderer = render_state.renderer.write();
-            renderer.free_texture(&self.texture_id);
+        match clicked {
+            Some(i) if toggle => {
+                if !selected_spheres.remove(&i) {
+                    selected_spheres.insert(i);
+                }
+            }
+            Some(i) if !(selected_spheres.len() == 1 && selected_spheres.contains(&i)) => {
+                *selected_spheres = [i].into();
+            }
+            None if toggle => {}
+            _ => selected_spheres.clear(),
+        }
+    }
 
-            let texture_size = wgpu::Extent3d {
-                width: width as _,
-                height: height as _,
-                depth_or_array_layers: 1,
-            };
+    /// Selects only the sphere after the current one, or before it when `backward`, wrapping around
+    fn cycle_selection(&mut self, backward: bool) {
+        let count = self.scene.spheres.len();
+        if count == 0 {
+            return;
+        }
+        let next = match self.selected_spheres.last() {
+            Some(&current) if backward => (current + count - 1) % count,
+            Some(&current) => (current + 1) % count,
+            None if backward => count - 1,
+            None => 0,
+        };
+        self.selected_spheres = [next].into();
+        self.scroll_to_selected = true;
+    }
 
-            let texture = render_state
-                .device
-                .create_texture(&wgpu::TextureDescriptor {
-                    size: texture_size,
-                    mip_level_count: 1,
-                    sample_count: 1,
-                    dimension: wgpu::TextureDimension::D2,
-                    format: wgpu::TextureFormat::Rgba8Unorm,
-                    usage: wgpu::TextureUsages::TEXTURE_BINDING
-                        | wgpu::TextureUsages::STORAGE_BINDING,
-                    label: Some("texture"),
-                    view_formats: &[],
-                });
+    /// The closest sphere under `pixel` in the viewport
+    fn pick_sphere(&self, pixel: egui::Vec2, size: egui::Vec2) -> Option<usize> {
+        let (origin, direction) = self.viewport_ray(pixel, size);
+        self.scene
+            .spheres
+            .iter()
+            .enumerate()
+            .filter_map(|(i, sphere)| Some((i, sphere.intersect(origin, direction)?)))
+            .min_by(|(_, a), (_, b)| a.total_cmp(b))
+            .map(|(i, _)| i)
+    }
 
-            self.texture_id = renderer.register_native_texture(
-                &render_state.device,
-                &texture.create_view(&wgpu::TextureViewDescriptor {
-                    ..Default::default()
-                }),
-                wgpu::FilterMode::Linear,
-            );
-
-            self.texture_bind_group =
-                render_state
-                    .device
-                    .create_bind_group(&wgpu::BindGroupDescriptor {
-                        label: Some("Texture bind group"),
-                        layout: &self.pipeline.get_bind_group_layout(0),
-                        entries: &[wgpu::BindGroupEntry {
-                            binding: 0,
-                            resource: wgpu::BindingResource::TextureView(
-                                &texture.create_view(&wgpu::TextureViewDescriptor::default()),
-                            ),
-                        }],
-                    });
+    /// Where `point` lands in the viewport, the inverse of `viewport_ray`, `None` if it's behind
+    /// the camera. Stereo isn't handled since a point shows up once per eye
+    fn project_to_viewport(
+        &self,
+        point: cgmath::Vector3<f32>,
+        size: egui::Vec2,
+    ) -> Option<egui::Vec2> {
+        let camera = &self.scene.camera;
+        let offset = point - camera.position;
+        let right = camera.rotation * cgmath::vec3(1.0, 0.0, 0.0);
+        let up = camera.rotation * cgmath::vec3(0.0, 1.0, 0.0);
+        let forward = camera.rotation * cgmath::vec3(0.0, 0.0, 1.0);
+        let depth = offset.dot(forward);
+        if depth <= 0.0 {
+            return None;
+        }
+        let aspect = size.x / size.y * camera.pixel_aspect;
+        let uv = cgmath::vec2(offset.dot(right) / depth / aspect, offset.dot(up) / depth);
+        Some(egui::vec2(
+            (uv.x + 1.0) * 0.5 * size.x,
+            (1.0 - uv.y) * 0.5 * size.y,
+        ))
+    }
 
-            self.texture_size = size;
+    /// The render texture size for a viewport of `size` points, scaled down to fit the max texture size
+    fn texture_size(
+        &self,
+        ctx: &egui::Context,
+        device: &wgpu::Device,
+        size: egui::Vec2,
+    ) -> (usize, usize) {
+        let mut size = size;
+        if self.settings.native_dpi {
+            size *= ctx.pixels_per_point();
         }
+        let max_size = self
+            .settings
+            .max_texture_size
+            .min(device.limits().max_texture_dimension_2d) as f32;
+        // keeps the aspect ratio so the image isn't stretched in the viewport
+        size *= (max_size / size.max_elem()).min(1.0);
+        (size.x as _, size.y as _)
+    }
 
-        // Update camera uniform
-        {
-            let camera_uniform: CameraUniform = self.camera.into();
-            let mut buffer =
-                UniformBuffer::new([0u8; <CameraUniform as ShaderSize>::SHADER_SIZE.get() as _]);
-            buffer.write(&camera_uniform).unwrap();
-            render_state
-                .queue
-                .write_buffer(&self.camera_buffer, 0, &buffer.into_inner());
+    /// The ray through `pixel` in the viewport, matching how the shader generates rays
+    fn viewport_ray(
+        &self,
+        pixel: egui::Vec2,
+        size: egui::Vec2,
+    ) -> (cgmath::Vector3<f32>, cgmath::Vector3<f32>) {
+        let camera = &self.scene.camera;
+        let (mut pixel, mut view_size, mut origin) = (pixel, size, camera.position);
+        let right = camera.rotation * cgmath::vec3(1.0, 0.0, 0.0);
+        let up = camera.rotation * cgmath::vec3(0.0, 1.0, 0.0);
+        let forward = camera.rotation * cgmath::vec3(0.0, 0.0, 1.0);
+        if self.settings.stereo {
+            view_size.x *= 0.5;
+            let mut eye = -0.5;
+            if pixel.x >= view_size.x {
+                pixel.x -= view_size.x;
+                eye = 0.5;
+            }
+            origin += right * eye * camera.eye_separation;
         }
+        let uv = cgmath::vec2(
+            pixel.x / view_size.x * 2.0 - 1.0,
+            1.0 - pixel.y / view_size.y * 2.0,
+        );
+        let aspect = view_size.x / view_size.y * camera.pixel_aspect;
+        let direction = (right * uv.x * aspect + up * uv.y + forward).normalize();
+        (origin, direction)
+    }
 
-        // Update spheres buffer
+    fn generate_ui(&mut self, ui: &mut egui::Ui) {
+        let options = &mut self.generate_options;
+        ui.horizontal(|ui| {
+            ui.radio_value(&mut options.layout, generate::Layout::Grid, "Grid");
+            ui.radio_value(
+                &mut options.layout,
+                generate::Layout::SphereSurface,
+                "Sphere Surface",
+            )
+            .on_hover_text(
+                "Spread evenly over a sphere on a Fibonacci lattice, the same count always gives the same layout",
+            );
+            ui.radio_value(
+                &mut options.layout,
+                generate::Layout::RandomBox,
+                "Random Box",
+            );
+        });
+        ui.horizontal(|ui| {
+            ui.label("Count:");
+            ui.add(egui::DragValue::new(&mut options.count).clamp_range(1..=10000));
+        });
+        ui.horizontal(|ui| {
+            ui.label("Center:");
+            ui.add(
+                egui::DragValue::new(&mut options.center.x)
+                    .prefix("x: ")
+                    .speed(0.1),
+            );
+            ui.add(
+                egui::DragValue::new(&mut options.center.y)
+                    .prefix("y: ")
+                    .speed(0.1),
+            );
+            ui.add(
+                egui::DragValue::new(&mut options.center.z)
+                    .prefix("z: ")
+                    .speed(0.1),
+            );
+        });
+        ui.horizontal(|ui| {
+            ui.label(match options.layout {
+                generate::Layout::Grid => "Spacing:",
+                generate::Layout::SphereSurface => "Surface Radius:",
+                generate::Layout::RandomBox => "Box Size:",
+            });
+            ui.add(egui::DragValue::new(&mut options.spacing).speed(0.1));
+        });
+        ui.horizontal(|ui| {
+            ui.label("Radius:");
+            ui.add(egui::DragValue::new(&mut options.radius).speed(0.01));
+        });
+        ui.horizontal(|ui| {
+            ui.checkbox(&mut options.random_colors, "Random Colors");
+            ui.add_enabled_ui(!options.random_colors, |ui| {
+                albedo_edit(ui, "Generate Albedo", &mut options.color);
+            });
+        });
+        if options.random_colors || options.layout == generate::Layout::RandomBox {
+            ui.horizontal(|ui| {
+                ui.label("Seed:");
+                ui.add(egui::DragValue::new(&mut options.seed));
+            });
+        }
+        // shrinking the count instead would change the layout, so leave that to the user
+        let room = self
+            .settings
+            .max_spheres
+            .saturating_sub(self.scene.spheres.len());
+        if ui
+            .add_enabled(options.count <= room, egui::Button::new("Generate"))
+            .on_disabled_hover_text(format!(
+                "Only {room} more spheres fit under the limit of {}, lower the count or raise Max Spheres",
+                self.settings.max_spheres
+            ))
+            .clicked()
         {
-            let mut buffer =
-                StorageBuffer::new(Vec::with_capacity(self.spheres_storage.size().get() as _));
-            buffer.write(&self.spheres_storage).unwrap();
-            let buffer = buffer.into_inner();
-            if self.spheres_buffer_size < buffer.len() {
-                self.spheres_buffer =
-                    render_state
-                        .device
-                        .create_buffer_init(&wgpu::util::BufferInitDescriptor {
-                            label: Some("Sphere Buffer"),
-                            contents: &buffer,
-                            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
-                        });
-
-                self.spheres_bind_group =
-                    render_state
-                        .device
-                        .create_bind_group(&wgpu::BindGroupDescriptor {
-                            layout: &self.pipeline.get_bind_group_layout(2),
-                            entries: &[wgpu::BindGroupEntry {
-                                binding: 0,
-                                resource: self.spheres_buffer.as_entire_binding(),
-                            }],
-                            label: Some("spheres_bind_group"),
-                        });
+            self.push_undo();
+            let spheres = generate::generate(&self.generate_options);
+            self.scene.spheres.extend(spheres);
+        }
+    }
 
-                self.spheres_buffer_size = buffer.len();
-            } else {
-                render_state
-                    .queue
-                    .write_buffer(&self.spheres_buffer, 0, &buffer);
+    fn bulk_edit_ui(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            ui.label("Albedo:");
+            albedo_edit(ui, "Bulk Albedo", &mut self.bulk_color);
+            if ui.button("Apply").clicked() {
+                self.push_undo();
+                for &i in &self.selected_spheres {
+                    self.scene.spheres[i].color = self.bulk_color.into();
+                }
+            }
+        });
+        ui.horizontal(|ui| {
+            ui.label("Offset:");
+            ui.add(
+                egui::DragValue::new(&mut self.bulk_offset.x)
+                    .prefix("x: ")
+                    .speed(0.1),
+            );
+            ui.add(
+                egui::DragValue::new(&mut self.bulk_offset.y)
+                    .prefix("y: ")
+                    .speed(0.1),
+            );
+            ui.add(
+                egui::DragValue::new(&mut self.bulk_offset.z)
+                    .prefix("z: ")
+                    .speed(0.1),
+            );
+            if ui.button("Apply").clicked() {
+                self.push_undo();
+                for &i in &self.selected_spheres {
+                    self.scene.spheres[i].position += self.bulk_offset;
+                }
             }
+        });
+    }
+
+    /// Moves the sphere at `from` to just before the one that was at `to`, keeping the selection
+    /// on the same spheres
+    fn move_sphere(&mut self, from: usize, to: usize) {
+        let to = if to > from { to - 1 } else { to };
+        if to == from || from >= self.scene.spheres.len() {
+            return;
         }
+        self.push_undo();
+        let sphere = self.scene.spheres.remove(from);
+        self.scene.spheres.insert(to, sphere);
+        if from < self.scene.sphere_names.len() {
+            let name = self.scene.sphere_names.remove(from);
+            let to = to.min(self.scene.sphere_names.len());
+            self.scene.sphere_names.insert(to, name);
+        }
+        self.scene.sync_sphere_ids();
+        let id = self.scene.sphere_ids.remove(from);
+        self.scene.sphere_ids.insert(to, id);
+        self.selected_spheres = self
+            .selected_spheres
+            .iter()
+            .map(|&i| match i {
+                i if i == from => to,
+                i if from < i && i <= to => i - 1,
+                i if to <= i && i < from => i + 1,
+                i => i,
+            })
+            .collect();
+    }
 
-        let mut encoder = render_state
-            .device
-            .create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+    fn push_undo(&mut self) {
+        self.undo_stack.push(UndoEntry {
+            spheres: self.scene.spheres.clone(),
+            sphere_names: self.scene.sphere_names.clone(),
+            sphere_ids: self.scene.sphere_ids.clone(),
+            camera_position: None,
+        });
+        self.scene_dirty = true;
+    }
+
+    fn undo(&mut self) {
+        if let Some(UndoEntry {
+            spheres,
+            sphere_names,
+            sphere_ids,
+            camera_position,
+        }) = self.undo_stack.pop()
         {
-            let workgroup_size = (16, 16);
-            let (dispatch_with, dispatch_height) = (
-                (self.texture_size.0 + workgroup_size.0 - 1) / workgroup_size.0,
-                (self.texture_size.1 + workgroup_size.1 - 1) / workgroup_size.1,
-            );
-            let mut compute_pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
-                label: Some("Compute pass"),
-            });
-            compute_pass.set_pipeline(&self.pipeline);
-            compute_pass.set_bind_group(0, &self.texture_bind_group, &[]);
-            compute_pass.set_bind_group(1, &self.camera_bind_group, &[]);
-            compute_pass.set_bind_group(2, &self.spheres_bind_group, &[]);
-            compute_pass.dispatch_workgroups(dispatch_with as _, dispatch_height as _, 1);
+            if let Some(camera_position) = camera_position {
+                self.scene.camera.position = camera_position;
+                self.camera_tween = None;
+                self.sync_orbit();
+            }
+            self.scene.spheres = spheres;
+            self.scene.sphere_names = sphere_names;
+            self.scene.sphere_ids = sphere_ids;
+            self.scene_dirty = true;
+            self.selected_spheres
+                .retain(|&i| i < self.scene.spheres.len());
         }
-        let submission_index = render_state.queue.submit([encoder.finish()]);
-
-        // this is slow but its just so the timings are a bit more accurate
-        render_state
-            .device
-            .poll(wgpu::Maintain::WaitForSubmissionIndex(submission_index));
+    }
 
-        self.last_frame_update_duration = start_frame_time.elapsed();
+    /// Moves the spheres and the camera together so the camera ends up at `origin`, which leaves
+    /// the view as it was
+    fn recenter_on_camera(&mut self, origin: cgmath::Vector3<f32>) {
+        // undo puts back the saved positions instead of moving back, so it is exact
+        self.undo_stack.push(UndoEntry {
+            spheres: self.scene.spheres.clone(),
+            sphere_names: self.scene.sphere_names.clone(),
+            sphere_ids: self.scene.sphere_ids.clone(),
+            camera_position: Some(self.scene.camera.position),
+        });
+        let offset = origin - self.scene.camera.position;
+        for sphere in &mut self.scene.spheres {
+            sphere.position += offset;
+        }
+        self.scene.camera.position = origin;
+        self.camera_tween = None;
+        self.sync_orbit();
     }
 
-    fn fixed_update(&mut self) {
+    fn fixed_update(&mut self, ts: f64) {
         let start_fixed_update_time = std::time::Instant::now();
 
-        // TODO: update stuff
+        if let Some(on_fixed_update) = &mut self.on_fixed_update {
+            on_fixed_update(&mut self.scene, ts);
+        }
 
         self.last_fixed_update_duration = start_fixed_update_time.elapsed();
     }
 }
 
 impl eframe::App for App {
+    fn save(&mut self, storage: &mut dyn eframe::Storage) {
+        eframe::set_value(storage, eframe::APP_KEY, &self.settings);
+    }
+
+    fn on_close_event(&mut self) -> bool {
+        self.track_edits();
+        if self.allowed_to_close || self.unsaved_scenes().is_empty() {
+            return true;
+        }
+        self.show_exit_confirm = true;
+        false
+    }
+
     fn update(&mut self, ctx: &egui::Context, frame: &mut eframe::Frame) {
         let start_time = std::time::Instant::now();
         let dt = start_time.duration_since(self.last_frame_time);
-        let ts = dt.as_secs_f64();
+        // the real frame time is still what gets reported in the stats
+        let frame_ts = dt.as_secs_f64();
+        let ts = dt.min(Self::MAX_FRAME_DELTA).as_secs_f64();
 
         self.fixed_update_time += ts;
-        while self.fixed_update_time >= Self::FIXED_UPDATE_TIMESTEP {
-            self.fixed_update();
-            self.fixed_update_time -= Self::FIXED_UPDATE_TIMESTEP;
+        // `ts` is clamped and the leftover is always under one step, so changing the rate can only
+        // ever cause a handful of catch-up steps
+        let fixed_update_timestep = 1.0 / self.settings.fixed_update_rate.max(1) as f64;
+        while self.fixed_update_time >= fixed_update_timestep {
+            self.fixed_update(fixed_update_timestep);
+            self.fixed_update_time -= fixed_update_timestep;
         }
 
-        egui::SidePanel::left("Counting").show(ctx, |ui| {
-            ui.label(format!("FPS: {:.3}", 1.0 / ts));
-            ui.label(format!(
-                "Render time: {:.3}ms",
-                self.last_frame_update_duration.as_secs_f64() * 1000.0
-            ));
-            ui.label(format!(
-                "Fixed update time: {:.3}ms",
-                self.last_fixed_update_duration.as_secs_f64() * 1000.0
-            ));
+        if self
+            .sphere_csv_watcher
+            .as_ref()
+            .is_some_and(|watcher| watcher.changed())
+        {
+            self.reload_sphere_csv();
+        }
+        if self
+            .camera_file_watcher
+            .as_ref()
+            .is_some_and(|watcher| watcher.changed())
+        {
+            self.reload_camera_file();
+        }
+        self.update_camera_file(ts);
 
-            ui.horizontal(|ui| {
-                ui.label("Up Sky Color:");
-                let mut up_sky_color = self.camera.up_sky_color.into();
-                egui::color_picker::color_edit_button_rgb(ui, &mut up_sky_color);
-                self.camera.up_sky_color = up_sky_color.into();
+        if let Some(turntable) = &mut self.turntable {
+            let render_state = frame.wgpu_render_state().unwrap();
+            match turntable.render_next(
+                &render_state.device,
+                &render_state.queue,
+                self.settings.png_color_space,
+            ) {
+                Ok(true) => {}
+                Ok(false) => self.turntable = None,
+                Err(error) => {
+                    self.banner = Some(format!("Failed to render turntable: {error}"));
+                    self.turntable = None;
+                }
+            }
+        }
+
+        self.track_edits();
+        if !self.show_key_help
+            && !ctx.wants_keyboard_input()
+            && ctx.input_mut(|i| i.consume_shortcut(&self.key_bindings.exit))
+        {
+            if self.show_exit_confirm {
+                self.show_exit_confirm = false;
+            } else if self.settings.escape_to_exit {
+                // goes through `on_close_event`, which asks first if anything is unsaved
+                frame.close();
+            }
+        }
+        if self.show_exit_confirm {
+            self.exit_confirm_ui(ctx, frame);
+        }
+
+        if self.show_key_help {
+            self.show_key_help = !ctx.input(|i| {
+                i.events
+                    .iter()
+                    .any(|event| matches!(event, egui::Event::Key { pressed: true, .. }))
             });
-            ui.horizontal(|ui| {
-                ui.label("Down Sky Color:");
-                let mut down_sky_color = self.camera.down_sky_color.into();
-                egui::color_picker::color_edit_button_rgb(ui, &mut down_sky_color);
-                self.camera.down_sky_color = down_sky_color.into();
+        } else if !ctx.wants_keyboard_input()
+            && ctx.input_mut(|i| i.consume_shortcut(&self.key_bindings.help))
+        {
+            self.show_key_help = true;
+        }
+        if self.show_key_help {
+            Self::key_help_ui(ctx, &self.key_bindings);
+        }
+
+        if !ctx.wants_keyboard_input() {
+            if ctx.input_mut(|i| i.consume_shortcut(&self.key_bindings.previous_sphere)) {
+                self.cycle_selection(true);
+            } else if ctx.input_mut(|i| i.consume_shortcut(&self.key_bindings.next_sphere)) {
+                self.cycle_selection(false);
+            }
+        }
+
+        if !ctx.wants_keyboard_input() && !self.selected_spheres.is_empty() {
+            let key_bindings = &self.key_bindings;
+            let mut offset = cgmath::vec3(0.0, 0.0, 0.0);
+            ctx.input_mut(|i| {
+                for (shortcut, direction) in key_bindings
+                    .nudge_horizontal
+                    .iter()
+                    .chain(&key_bindings.nudge_vertical)
+                {
+                    if i.consume_shortcut(shortcut) {
+                        offset += cgmath::Vector3::from(*direction) * self.settings.nudge_step;
+                    }
+                }
             });
+            if offset != cgmath::vec3(0.0, 0.0, 0.0) {
+                self.push_undo();
+                for &index in &self.selected_spheres {
+                    self.scene.spheres[index].position += offset;
+                }
+            }
+        }
 
+        for error in self.gpu_errors.try_iter() {
+            self.last_error = Some(error);
+        }
+        if self.banner.is_some() && self.banner != self.last_error {
+            self.last_error = self.banner.clone();
+        }
+        egui::TopBottomPanel::bottom("Status Bar").show(ctx, |ui| {
             ui.horizontal(|ui| {
-                ui.label("Min Distance:");
-                ui.add(egui::DragValue::new(&mut self.camera.min_distance).speed(0.001));
-                self.camera.min_distance = self.camera.min_distance.max(0.0001);
+                let is_mac = ctx.os() == egui::os::OperatingSystem::Mac;
+                ui.label(format!(
+                    "Press {} for controls",
+                    KeyBindings::format(self.key_bindings.help, is_mac)
+                ));
+                if self.camera_locked {
+                    ui.separator();
+                    ui.label("Camera locked");
+                }
+                if let Some(error) = &self.last_error {
+                    ui.separator();
+                    ui.colored_label(egui::Color32::RED, error.lines().next().unwrap_or(""))
+                        .on_hover_text(error);
+                }
             });
-            ui.horizontal(|ui| {
-                ui.label("Max Distance:");
-                ui.add(egui::DragValue::new(&mut self.camera.max_distance).speed(1.0));
-                self.camera.max_distance = self.camera.max_distance.max(0.0);
+        });
+
+        egui::TopBottomPanel::top("Tabs").show(ctx, |ui| self.tabs_ui(ui));
+
+        if let Some(banner) = &self.banner {
+            let mut dismissed = false;
+            egui::TopBottomPanel::top("Banner").show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    ui.colored_label(egui::Color32::RED, banner);
+                    dismissed = ui.button("Dismiss").clicked();
+                });
             });
+            if dismissed {
+                self.banner = None;
+            }
+        }
 
-            ui.collapsing("Spheres", |ui| {
-                if ui.button("Add Sphere").clicked() {
-                    self.spheres_storage.spheres.push(Sphere::default());
-                }
-                egui::ScrollArea::new([false, true]).show(ui, |ui| {
-                    let mut i = 0;
-                    while i < self.spheres_storage.spheres.len() {
-                        let sphere = &mut self.spheres_storage.spheres[i as usize];
-                        let mut to_remove = false;
-                        ui.collapsing(format!("Sphere {i}"), |ui| {
-                            ui.horizontal(|ui| {
-                                ui.label("Position:");
-                                ui.add(
-                                    egui::DragValue::new(&mut sphere.position.x)
-                                        .prefix("x: ")
-                                        .speed(0.1),
-                                );
-                                ui.add(
-                                    egui::DragValue::new(&mut sphere.position.y)
-                                        .prefix("y: ")
-                                        .speed(0.1),
-                                );
-                                ui.add(
-                                    egui::DragValue::new(&mut sphere.position.z)
-                                        .prefix("z: ")
-                                        .speed(0.1),
-                                );
-                            });
-                            ui.horizontal(|ui| {
-                                ui.label("Radius:");
-                                ui.add(egui::DragValue::new(&mut sphere.radius).speed(0.1));
-                            });
-                            ui.horizontal(|ui| {
-                                ui.label("Color:");
-                                let mut color = sphere.color.into();
-                                egui::color_picker::color_edit_button_rgb(ui, &mut color);
-                                sphere.color = color.into();
-                            });
-                            if ui.button("Delete").clicked() {
-                                to_remove = true;
-                            }
-                        });
-                        if to_remove {
-                            self.spheres_storage.spheres.remove(i as _);
-                        } else {
-                            i += 1;
-                        }
+        if let Some(probe) = &self.probe {
+            let mut open = true;
+            egui::Window::new("Probe")
+                .open(&mut open)
+                .resizable(false)
+                .show(ctx, |ui| {
+                    ui.monospace(probe);
+                    if ui.button("Copy").clicked() {
+                        ui.output_mut(|output| output.copied_text = probe.clone());
                     }
-                    ui.allocate_space(ui.available_size());
+                });
+            if !open {
+                self.probe = None;
+            }
+        }
+
+        egui::Window::new("Shader Source")
+            .open(&mut self.show_shader_source)
+            .default_size([600.0, 400.0])
+            .show(ctx, |ui| {
+                let mut source = self.renderer.shader_source();
+                if ui.button("Copy").clicked() {
+                    ui.output_mut(|output| output.copied_text = source.to_owned());
+                }
+                egui::ScrollArea::both().show(ui, |ui| {
+                    // a &str can be selected and copied from but not edited
+                    ui.add(
+                        egui::TextEdit::multiline(&mut source)
+                            .code_editor()
+                            .desired_width(f32::INFINITY),
+                    );
+                });
+            });
+
+        let panel_side = if self.settings.panel_on_right {
+            egui::panel::Side::Right
+        } else {
+            egui::panel::Side::Left
+        };
+        egui::SidePanel::new(panel_side, "Counting")
+            .resizable(true)
+            .show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    ui.checkbox(&mut self.settings.panel_on_right, "Dock Panel Right");
+                    ui.checkbox(&mut self.settings.escape_to_exit, "Escape To Exit")
+                        .on_hover_text("Close the app with Escape, asking first if anything is unsaved");
+                });
+
+                if self.scene.spheres.len() > self.settings.max_spheres {
+                    ui.colored_label(
+                        egui::Color32::YELLOW,
+                        format!(
+                            "There are {} spheres, more than the limit of {}, rendering may hang the GPU",
+                            self.scene.spheres.len(),
+                            self.settings.max_spheres
+                        ),
+                    );
+                }
+
+                egui::CollapsingHeader::new("Stats")
+                    .default_open(true)
+                    .show(ui, |ui| self.stats_ui(ui, frame_ts));
+                ui.collapsing("Scene", |ui| {
+                    self.scene_ui(ui);
+                    self.sphere_csv_ui(ui);
+                });
+                egui::CollapsingHeader::new("Camera")
+                    .default_open(true)
+                    .show(ui, |ui| {
+                        self.camera_ui(ui);
+                        self.camera_file_ui(ui);
+                    });
+                ui.collapsing("Render", |ui| self.render_ui(ui));
+                ui.collapsing("Post", |ui| self.post_ui(ui));
+                if ui
+                    .button("Reset Settings")
+                    .on_hover_text("Restore the render and post settings, the scene is kept")
+                    .clicked()
+                {
+                    self.reset_settings();
+                }
+                ui.collapsing("Compare", |ui| {
+                    self.compare_ui(ui, frame.wgpu_render_state().unwrap())
+                });
+                ui.collapsing("Backplate", |ui| {
+                    self.backplate_ui(ui, frame.wgpu_render_state().unwrap())
+                });
+                ui.collapsing("Export", |ui| {
+                    self.export_ui(ui, frame.wgpu_render_state().unwrap())
+                });
+                ui.collapsing("Spheres", |ui| self.spheres_ui(ui));
+                ui.collapsing("GPU Info", |ui| {
+                    self.gpu_info_ui(ui, &frame.wgpu_render_state().unwrap().device)
                 });
             });
-        });
         egui::CentralPanel::default()
             .frame(egui::Frame::none())
             .show(ctx, |ui| {
                 let size = ui.available_size();
-                self.render(
-                    ts,
-                    frame.wgpu_render_state().unwrap(),
-                    (size.x as _, size.y as _),
-                );
-                ui.image(self.texture_id, size);
+                let render_state = frame.wgpu_render_state().unwrap();
+                let texture_size = self.texture_size(ctx, &render_state.device, size);
+                self.render(ts, render_state, texture_size);
+                let response =
+                    ui.add(egui::Image::new(self.texture_id, size).sense(egui::Sense::click()));
+                if self.settings.show_sphere_labels && !self.settings.stereo {
+                    for (i, sphere) in self.scene.spheres.iter().enumerate() {
+                        if let Some(pixel) = self.project_to_viewport(sphere.position, size) {
+                            ui.painter().text(
+                                response.rect.min + pixel,
+                                egui::Align2::CENTER_CENTER,
+                                self.scene.sphere_label(i),
+                                egui::FontId::default(),
+                                egui::Color32::WHITE,
+                            );
+                        }
+                    }
+                }
+                if let Some((_, texture_id)) = &self.compare_texture {
+                    // a frozen render from a different viewport size just gets stretched
+                    let rect = response.rect;
+                    let split_x = egui::lerp(rect.x_range(), self.compare_split);
+                    ui.painter().image(
+                        *texture_id,
+                        egui::Rect::from_min_max(rect.min, egui::pos2(split_x, rect.max.y)),
+                        egui::Rect::from_min_max(
+                            egui::pos2(0.0, 0.0),
+                            egui::pos2(self.compare_split, 1.0),
+                        ),
+                        egui::Color32::WHITE,
+                    );
+                    split_line_ui(ui, rect, &mut self.compare_split, "Compare Split");
+                }
+                if self.split_camera.is_some() {
+                    split_line_ui(ui, response.rect, &mut self.camera_split, "Camera Split");
+                }
+                if let (Some(orbit), None, false) =
+                    (&mut self.orbit, &self.camera_tween, self.camera_locked)
+                {
+                    let rect = response.rect;
+                    // the ball fits the shorter side of the viewport
+                    let to_ball = |position: egui::Pos2| {
+                        let offset = (position - rect.center()) / (rect.size().min_elem() * 0.5);
+                        cgmath::vec2(offset.x, -offset.y)
+                    };
+                    ui.input(|i| {
+                        let delta = i.pointer.delta();
+                        if !i.pointer.secondary_down()
+                            || !response.hovered()
+                            || delta == egui::Vec2::ZERO
+                        {
+                            return;
+                        }
+                        if let Some(position) = i.pointer.interact_pos() {
+                            orbit.arcball(to_ball(position - delta), to_ball(position));
+                        }
+                    });
+                }
+                if response.clicked() {
+                    if let Some(position) = response.interact_pointer_pos() {
+                        let pixel = position - response.rect.min;
+                        if ui.input(|input| input.modifiers.shift) {
+                            let (origin, direction) = self.viewport_ray(pixel, size);
+                            let trace = probe::probe(
+                                &self.scene.spheres_buffer().spheres,
+                                &self.scene.camera.into(),
+                                origin,
+                                direction,
+                            );
+                            self.probe =
+                                Some(format!("pixel: ({:.0}, {:.0})\n{trace}", pixel.x, pixel.y));
+                        } else {
+                            let clicked = self.pick_sphere(pixel, size);
+                            let toggle = ui.input(|input| input.modifiers.command);
+                            Self::click_select(&mut self.selected_spheres, clicked, toggle);
+                        }
+                    }
+                }
             });
 
-        if !ctx.wants_pointer_input() {
+        if !ctx.wants_keyboard_input() {
+            for (index, key) in self.key_bindings.bookmarks.into_iter().enumerate() {
+                if ctx.input(|i| i.key_pressed(key)) {
+                    if ctx.input(|i| KeyBindings::held(i, self.key_bindings.save_bookmark)) {
+                        self.save_bookmark(index);
+                    } else if !self.camera_locked {
+                        self.go_to_bookmark(index);
+                    }
+                }
+            }
+        }
+
+        // the tween takes over from orbiting until it's done
+        let tweened_camera = self.camera_tween.as_mut().map(|tween| {
+            let finished = tween.step(ts as f32, &mut self.scene.camera);
+            (finished, self.scene.camera)
+        });
+        if let Some((true, _)) = tweened_camera {
+            self.camera_tween = None;
+            self.sync_orbit();
+        }
+
+        if let (Some(orbit), None) = (&self.orbit, &self.camera_tween) {
+            orbit.apply(&mut self.scene.camera);
+        } else if self.orbit.is_none() && !self.camera_locked && !ctx.wants_pointer_input() {
             ctx.input(|i| {
                 if i.pointer.secondary_down() {
                     let rotation_horizontal = cgmath::Quaternion::from_angle_y(cgmath::Deg(
@@ -495,72 +3056,123 @@ impl eframe::App for App {
                     let rotation_vertical = cgmath::Quaternion::from_angle_x(cgmath::Deg(
                         i.pointer.velocity().y * ts as f32,
                     ));
-                    self.camera.rotation = self.camera.rotation * rotation_horizontal;
-                    self.camera.rotation = self.camera.rotation * rotation_vertical;
+                    self.scene.camera.rotation = self.scene.camera.rotation * rotation_horizontal;
+                    self.scene.camera.rotation = self.scene.camera.rotation * rotation_vertical;
                 }
             });
         }
 
-        if !ctx.wants_keyboard_input() {
+        if self.orbit.is_none() && !self.camera_locked && !ctx.wants_keyboard_input() {
+            let key_bindings = self.key_bindings;
             ctx.input(|i| {
+                // the nudge shortcuts share the arrows, those presses move the selection instead
+                let look_down = |key| key_bindings.look_down(i, key);
                 let rotation_horizontal = cgmath::Quaternion::from_angle_y(cgmath::Deg(
-                    if i.key_down(egui::Key::ArrowLeft) {
+                    if look_down(key_bindings.look_left) {
                         -90.0 * ts as f32
-                    } else if i.key_down(egui::Key::ArrowRight) {
+                    } else if look_down(key_bindings.look_right) {
                         90.0 * ts as f32
                     } else {
                         0.0
                     },
                 ));
                 let rotation_vertical = cgmath::Quaternion::from_angle_x(cgmath::Deg(
-                    if i.key_down(egui::Key::ArrowUp) {
+                    if look_down(key_bindings.look_up) {
                         -90.0 * ts as f32
-                    } else if i.key_down(egui::Key::ArrowDown) {
+                    } else if look_down(key_bindings.look_down) {
                         90.0 * ts as f32
                     } else {
                         0.0
                     },
                 ));
-                let rotation_roll =
-                    cgmath::Quaternion::from_angle_z(cgmath::Deg(if i.key_down(egui::Key::Q) {
+                let rotation_roll = cgmath::Quaternion::from_angle_z(cgmath::Deg(
+                    if i.key_down(key_bindings.roll_left) {
                         90.0 * ts as f32
-                    } else if i.key_down(egui::Key::E) {
+                    } else if i.key_down(key_bindings.roll_right) {
                         -90.0 * ts as f32
                     } else {
                         0.0
-                    }));
-                self.camera.rotation = self.camera.rotation * rotation_horizontal;
-                self.camera.rotation = self.camera.rotation * rotation_vertical;
-                self.camera.rotation = self.camera.rotation * rotation_roll;
+                    },
+                ));
+                self.scene.camera.rotation = self.scene.camera.rotation * rotation_horizontal;
+                self.scene.camera.rotation = self.scene.camera.rotation * rotation_vertical;
+                self.scene.camera.rotation = self.scene.camera.rotation * rotation_roll;
 
                 const CAMERA_SPEED: f32 = 2.0;
 
-                let forward = self.camera.rotation * cgmath::vec3(0.0, 0.0, 1.0);
-                let right = self.camera.rotation * cgmath::vec3(1.0, 0.0, 0.0);
-                let up = self.camera.rotation * cgmath::vec3(0.0, 1.0, 0.0);
+                let forward = self.scene.camera.rotation * cgmath::vec3(0.0, 0.0, 1.0);
+                let right = self.scene.camera.rotation * cgmath::vec3(1.0, 0.0, 0.0);
+                let up = self.scene.camera.rotation * cgmath::vec3(0.0, 1.0, 0.0);
 
-                if i.key_down(egui::Key::W) {
-                    self.camera.position += CAMERA_SPEED * forward * ts as f32;
+                if i.key_down(key_bindings.forward) {
+                    self.scene.camera.position += CAMERA_SPEED * forward * ts as f32;
                 }
-                if i.key_down(egui::Key::S) {
-                    self.camera.position -= CAMERA_SPEED * forward * ts as f32;
+                if i.key_down(key_bindings.back) {
+                    self.scene.camera.position -= CAMERA_SPEED * forward * ts as f32;
                 }
-                if i.key_down(egui::Key::A) {
-                    self.camera.position -= CAMERA_SPEED * right * ts as f32;
+                if i.key_down(key_bindings.left) {
+                    self.scene.camera.position -= CAMERA_SPEED * right * ts as f32;
                 }
-                if i.key_down(egui::Key::D) {
-                    self.camera.position += CAMERA_SPEED * right * ts as f32;
+                if i.key_down(key_bindings.right) {
+                    self.scene.camera.position += CAMERA_SPEED * right * ts as f32;
                 }
-                if i.modifiers.ctrl {
-                    self.camera.position -= CAMERA_SPEED * up * ts as f32;
+                if KeyBindings::held(i, key_bindings.down) {
+                    self.scene.camera.position -= CAMERA_SPEED * up * ts as f32;
                 }
-                if i.key_down(egui::Key::Space) {
-                    self.camera.position += CAMERA_SPEED * up * ts as f32;
+                if i.key_down(key_bindings.up) {
+                    self.scene.camera.position += CAMERA_SPEED * up * ts as f32;
                 }
             });
         }
 
+        // adds to the keyboard rather than replacing it, and doesn't care about focus
+        #[cfg(feature = "gamepad")]
+        if self.orbit.is_none() && !self.camera_locked {
+            if let Some((left_stick, right_stick)) =
+                self.gamepad.sticks(self.settings.gamepad_deadzone)
+            {
+                let look = self.settings.gamepad_look_speed * ts as f32;
+                let camera = &mut self.scene.camera;
+                camera.rotation = camera.rotation
+                    * cgmath::Quaternion::from_angle_y(cgmath::Deg(right_stick[0] * look))
+                    * cgmath::Quaternion::from_angle_x(cgmath::Deg(-right_stick[1] * look));
+
+                let forward = camera.rotation * cgmath::vec3(0.0, 0.0, 1.0);
+                let right = camera.rotation * cgmath::vec3(1.0, 0.0, 0.0);
+                camera.position += (forward * left_stick[1] + right * left_stick[0])
+                    * self.settings.gamepad_move_speed
+                    * ts as f32;
+            }
+        }
+
+        // moving the camera by hand cancels the tween
+        if let Some((_, camera)) = tweened_camera {
+            if camera.position != self.scene.camera.position
+                || camera.rotation != self.scene.camera.rotation
+            {
+                self.camera_tween = None;
+            }
+        }
+
+        if let Err(error) = self.write_frame_stats(frame_ts) {
+            self.frame_stats_file = None;
+            self.frame_stats_error = Some(error.to_string());
+        }
+
         self.last_frame_time = start_time;
-        ctx.request_repaint();
+        // regaining focus triggers a repaint by itself, and the clamped frame delta stops anything jumping
+        // a running turntable keeps going at full speed in the background
+        if self.settings.throttle_when_unfocused
+            && !ctx.input(|i| i.raw.has_focus)
+            && self.turntable.is_none()
+        {
+            if self.settings.unfocused_fps > 0.0 {
+                ctx.request_repaint_after(std::time::Duration::from_secs_f32(
+                    1.0 / self.settings.unfocused_fps,
+                ));
+            }
+        } else {
+            ctx.request_repaint();
+        }
     }
 }
@@ -0,0 +1,47 @@
+@group(0)
+@binding(0)
+var hdr_texture: texture_2d<f32>;
+
+@group(0)
+@binding(1)
+var<storage, read_write> average_luminance: f32;
+
+const THREAD_COUNT: u32 = 256u;
+// a sparse sample is plenty for an average and keeps this cheap at any resolution
+const MAX_SAMPLES: u32 = 65536u;
+
+var<workgroup> log_sums: array<f32, 256>;
+var<workgroup> sample_counts: array<u32, 256>;
+
+// a single workgroup averages the log luminance, so one bright pixel can't dominate
+@compute
+@workgroup_size(256)
+fn main(
+    @builtin(local_invocation_index) index: u32
+) {
+    let size = vec2<u32>(textureDimensions(hdr_texture));
+    let pixel_count = size.x * size.y;
+    let step = max(pixel_count / MAX_SAMPLES, 1u);
+
+    var log_sum = 0.0;
+    var sample_count = 0u;
+    for (var i = index * step; i < pixel_count; i += THREAD_COUNT * step) {
+        let color = textureLoad(hdr_texture, vec2<i32>(vec2<u32>(i % size.x, i / size.x)), 0).rgb;
+        let luminance = dot(color, vec3<f32>(0.2126, 0.7152, 0.0722));
+        log_sum += log(max(luminance, 0.0001));
+        sample_count += 1u;
+    }
+    log_sums[index] = log_sum;
+    sample_counts[index] = sample_count;
+    workgroupBarrier();
+
+    if index == 0u {
+        var total = 0.0;
+        var total_count = 0u;
+        for (var i = 0u; i < THREAD_COUNT; i++) {
+            total += log_sums[i];
+            total_count += sample_counts[i];
+        }
+        average_luminance = exp(total / f32(max(total_count, 1u)));
+    }
+}
@@ -0,0 +1,118 @@
+use crate::shader_types::Sphere;
+use std::io::Write;
+
+pub struct ObjOptions {
+    /// Latitude bands per sphere, with twice as many segments around
+    pub subdivisions: u32,
+    /// Put every sphere in one object instead of an object each
+    pub merge: bool,
+}
+
+/// Tessellates every sphere into a uv sphere and writes them to an obj, along with a `.mtl` next to it
+/// that has a material per sphere. Csg is ignored, both spheres of a pair get exported whole
+pub fn save_obj(
+    path: &std::path::Path,
+    spheres: &[Sphere],
+    options: &ObjOptions,
+) -> Result<(), String> {
+    let mtl_path = path.with_extension("mtl");
+    let create = |path: &std::path::Path| {
+        std::fs::File::create(path)
+            .map(std::io::BufWriter::new)
+            .map_err(|error| format!("failed to save '{}': {error}", path.display()))
+    };
+    let mut obj = create(path)?;
+    let mut mtl = create(&mtl_path)?;
+
+    let mtl_name = mtl_path.file_name().unwrap_or_default().to_string_lossy();
+    write_obj(&mut obj, &mtl_name, spheres, options)
+        .and_then(|()| obj.flush())
+        .map_err(|error| format!("failed to save '{}': {error}", path.display()))?;
+    write_mtl(&mut mtl, spheres)
+        .and_then(|()| mtl.flush())
+        .map_err(|error| format!("failed to save '{}': {error}", mtl_path.display()))
+}
+
+fn write_obj(
+    obj: &mut impl Write,
+    mtl_name: &str,
+    spheres: &[Sphere],
+    options: &ObjOptions,
+) -> std::io::Result<()> {
+    let rings = options.subdivisions.max(2);
+    let segments = rings * 2;
+
+    // a vertex at each pole and a ring of them at every latitude in between
+    let mut directions = vec![cgmath::vec3(0.0, 1.0, 0.0)];
+    for ring in 1..rings {
+        let theta = std::f32::consts::PI * ring as f32 / rings as f32;
+        for segment in 0..segments {
+            let phi = std::f32::consts::TAU * segment as f32 / segments as f32;
+            directions.push(cgmath::vec3(
+                theta.sin() * phi.cos(),
+                theta.cos(),
+                theta.sin() * phi.sin(),
+            ));
+        }
+    }
+    directions.push(cgmath::vec3(0.0, -1.0, 0.0));
+
+    writeln!(obj, "mtllib {mtl_name}")?;
+    if options.merge {
+        writeln!(obj, "o spheres")?;
+    }
+    for (index, sphere) in spheres.iter().enumerate() {
+        if !options.merge {
+            writeln!(obj, "o sphere_{index}")?;
+        }
+        writeln!(obj, "usemtl sphere_{index}")?;
+
+        // mirrored along z like the gltf import, other tools look down -z
+        let center = cgmath::vec3(sphere.position.x, sphere.position.y, -sphere.position.z);
+        for &direction in &directions {
+            let position = center + direction * sphere.radius.abs();
+            writeln!(obj, "v {} {} {}", position.x, position.y, position.z)?;
+        }
+        for direction in &directions {
+            writeln!(obj, "vn {} {} {}", direction.x, direction.y, direction.z)?;
+        }
+
+        // obj indices are 1 based and count every vertex in the file so far
+        let base = index as u32 * directions.len() as u32 + 1;
+        let ring_vertex = |ring: u32, segment: u32| base + 1 + ring * segments + segment % segments;
+        let bottom = base + directions.len() as u32 - 1;
+        for segment in 0..segments {
+            let (a, b) = (ring_vertex(0, segment), ring_vertex(0, segment + 1));
+            writeln!(obj, "f {base}//{base} {b}//{b} {a}//{a}")?;
+            for ring in 0..rings - 2 {
+                let (c, d) = (ring_vertex(ring, segment), ring_vertex(ring, segment + 1));
+                let (e, f) = (
+                    ring_vertex(ring + 1, segment + 1),
+                    ring_vertex(ring + 1, segment),
+                );
+                writeln!(obj, "f {c}//{c} {d}//{d} {e}//{e} {f}//{f}")?;
+            }
+            let (a, b) = (
+                ring_vertex(rings - 2, segment),
+                ring_vertex(rings - 2, segment + 1),
+            );
+            writeln!(obj, "f {bottom}//{bottom} {a}//{a} {b}//{b}")?;
+        }
+    }
+    Ok(())
+}
+
+fn write_mtl(mtl: &mut impl Write, spheres: &[Sphere]) -> std::io::Result<()> {
+    for (index, sphere) in spheres.iter().enumerate() {
+        let emission = sphere.emission * sphere.emission_strength;
+        writeln!(mtl, "newmtl sphere_{index}")?;
+        writeln!(
+            mtl,
+            "Kd {} {} {}",
+            sphere.color.x, sphere.color.y, sphere.color.z
+        )?;
+        writeln!(mtl, "Ke {} {} {}", emission.x, emission.y, emission.z)?;
+        writeln!(mtl, "d {}", sphere.opacity.clamp(0.0, 1.0))?;
+    }
+    Ok(())
+}
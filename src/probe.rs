@@ -0,0 +1,225 @@
+use crate::shader_types::{
+    CameraUniform, Sphere, SPHERE_FLAG_CSG_INTERSECT, SPHERE_FLAG_CSG_SUBTRACT,
+    SPHERE_FLAG_SMOOTH_NORMALS, SPHERE_FLAG_TWO_SIDED,
+};
+use cgmath::{InnerSpace, Vector3};
+
+// must match `get_color` in shader.wgsl
+const MAX_LAYERS: usize = 4;
+const LIGHT_DIRECTION: Vector3<f32> = Vector3::new(0.3, -1.0, 0.4);
+
+/// Where a probed ray reached a surface
+pub struct ProbeHit {
+    /// Index of the first sphere of the shape that was hit
+    pub sphere: usize,
+    pub distance: f32,
+    pub position: Vector3<f32>,
+    pub normal: Vector3<f32>,
+}
+
+/// A cpu replay of what the shader does for a single ray, ignoring the selection outline and post effects
+pub struct ProbeTrace {
+    pub origin: Vector3<f32>,
+    pub direction: Vector3<f32>,
+    pub hit: Option<ProbeHit>,
+    /// What blocked the shadow ray from `hit` towards the light
+    pub shadow_hit: Option<ProbeHit>,
+    pub color: Vector3<f32>,
+}
+
+impl std::fmt::Display for ProbeTrace {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let vector = |v: Vector3<f32>| format!("({:.4}, {:.4}, {:.4})", v.x, v.y, v.z);
+        writeln!(f, "origin: {}", vector(self.origin))?;
+        writeln!(f, "direction: {}", vector(self.direction))?;
+        for (name, hit) in [("hit", &self.hit), ("shadow hit", &self.shadow_hit)] {
+            match hit {
+                Some(hit) => writeln!(
+                    f,
+                    "{name}: sphere {} at distance {:.4}, position {}, normal {}",
+                    hit.sphere,
+                    hit.distance,
+                    vector(hit.position),
+                    vector(hit.normal)
+                )?,
+                None => writeln!(f, "{name}: none")?,
+            }
+        }
+        write!(f, "color: {}", vector(self.color))
+    }
+}
+
+fn apply_fog(camera: &CameraUniform, color: Vector3<f32>, distance: f32) -> Vector3<f32> {
+    let t = (-camera.fog_density * distance).exp();
+    camera.fog_color * (1.0 - t) + color * t
+}
+
+fn sphere_sdf(position: Vector3<f32>, sphere: &Sphere) -> f32 {
+    (position - sphere.position).magnitude() - sphere.radius
+}
+
+fn shape_size(spheres: &[Sphere], index: usize) -> usize {
+    let csg_flags = SPHERE_FLAG_CSG_SUBTRACT | SPHERE_FLAG_CSG_INTERSECT;
+    if spheres[index].flags & csg_flags != 0 && index + 1 < spheres.len() {
+        2
+    } else {
+        1
+    }
+}
+
+fn shape_sdf(spheres: &[Sphere], position: Vector3<f32>, index: usize) -> f32 {
+    let sphere = &spheres[index];
+    let dist = sphere_sdf(position, sphere);
+    if shape_size(spheres, index) == 1 {
+        return dist;
+    }
+
+    let other_dist = sphere_sdf(position, &spheres[index + 1]);
+    if sphere.flags & SPHERE_FLAG_CSG_SUBTRACT != 0 {
+        dist.max(-other_dist)
+    } else {
+        dist.max(other_dist)
+    }
+}
+
+/// The distance to the closest shape and the index of its first sphere, shapes in `skipped` are ignored
+fn closest_shape(spheres: &[Sphere], position: Vector3<f32>, skipped: &[usize]) -> (f32, usize) {
+    let mut closest = (f32::INFINITY, 0);
+    let mut i = 0;
+    while i < spheres.len() {
+        if !skipped.contains(&i) {
+            let dist = shape_sdf(spheres, position, i);
+            if dist < closest.0 {
+                closest = (dist, i);
+            }
+        }
+        i += shape_size(spheres, i);
+    }
+    closest
+}
+
+fn normal(
+    spheres: &[Sphere],
+    camera: &CameraUniform,
+    p: Vector3<f32>,
+    direction: Vector3<f32>,
+    sphere: usize,
+) -> Vector3<f32> {
+    // the analytic normal is only right for a plain sphere
+    let normal = if spheres[sphere].flags & SPHERE_FLAG_SMOOTH_NORMALS != 0
+        && shape_size(spheres, sphere) == 1
+    {
+        (p - spheres[sphere].position).normalize()
+    } else {
+        let sdf = |p| shape_sdf(spheres, p, sphere);
+        let e = camera.min_distance;
+        cgmath::vec3(
+            sdf(p + cgmath::vec3(e, 0.0, 0.0)) - sdf(p - cgmath::vec3(e, 0.0, 0.0)),
+            sdf(p + cgmath::vec3(0.0, e, 0.0)) - sdf(p - cgmath::vec3(0.0, e, 0.0)),
+            sdf(p + cgmath::vec3(0.0, 0.0, e)) - sdf(p - cgmath::vec3(0.0, 0.0, e)),
+        )
+        .normalize()
+    };
+    if spheres[sphere].flags & SPHERE_FLAG_TWO_SIDED != 0 && normal.dot(direction) > 0.0 {
+        -normal
+    } else {
+        normal
+    }
+}
+
+/// Marches on from `origin`, which is already `distance` along the ray
+fn march(
+    spheres: &[Sphere],
+    camera: &CameraUniform,
+    mut origin: Vector3<f32>,
+    direction: Vector3<f32>,
+    mut distance: f32,
+    skipped: &[usize],
+) -> Option<ProbeHit> {
+    if spheres.is_empty() {
+        return None;
+    }
+    while distance < camera.max_distance {
+        let (dist, sphere) = closest_shape(spheres, origin, skipped);
+        origin += direction * dist;
+        distance += dist;
+        if dist < camera.min_distance {
+            return Some(ProbeHit {
+                sphere,
+                distance,
+                position: origin,
+                normal: normal(spheres, camera, origin, direction, sphere),
+            });
+        }
+    }
+    None
+}
+
+/// Traces one ray through packed `spheres` the same way the shader does
+pub fn probe(
+    spheres: &[Sphere],
+    camera: &CameraUniform,
+    origin: Vector3<f32>,
+    direction: Vector3<f32>,
+) -> ProbeTrace {
+    let light_direction = LIGHT_DIRECTION.normalize();
+    let mut first_hit = None;
+    // see-through shapes get blended in and skipped for the rest of the ray
+    let mut layers = Vec::new();
+    let mut color = cgmath::vec3(0.0, 0.0, 0.0);
+    let mut transmittance = 1.0;
+    let (mut march_origin, mut distance) = (origin, 0.0);
+    loop {
+        let Some(hit) = march(spheres, camera, march_origin, direction, distance, &layers) else {
+            let t = direction.y * 0.5 + 0.5;
+            let mut sky_color = camera.up_sky_color * (1.0 - t) + camera.down_sky_color * t;
+            if camera.fog_sky != 0 {
+                sky_color = apply_fog(camera, sky_color, camera.max_distance);
+            }
+            color += sky_color * transmittance;
+            break;
+        };
+
+        let shadow_hit = march(
+            spheres,
+            camera,
+            hit.position + hit.normal * camera.shadow_bias,
+            -light_direction,
+            0.0,
+            &[],
+        );
+        let sphere = &spheres[hit.sphere];
+        let light_amount = if shadow_hit.is_some() {
+            0.0
+        } else {
+            hit.normal.dot(-light_direction)
+        }
+        .max(0.05);
+        let surface_color = apply_fog(
+            camera,
+            sphere.color * light_amount + sphere.emission * sphere.emission_strength,
+            hit.distance,
+        );
+        let opacity = sphere.opacity.clamp(0.0, 1.0);
+        let opaque = opacity >= 1.0 || layers.len() == MAX_LAYERS;
+        color += surface_color * transmittance * if opaque { 1.0 } else { opacity };
+        transmittance *= 1.0 - opacity;
+        (march_origin, distance) = (hit.position, hit.distance);
+        layers.push(hit.sphere);
+        if first_hit.is_none() {
+            first_hit = Some((hit, shadow_hit));
+        }
+        if opaque {
+            break;
+        }
+    }
+
+    let (hit, shadow_hit) = first_hit.unzip();
+    ProbeTrace {
+        origin,
+        direction,
+        hit,
+        shadow_hit: shadow_hit.flatten(),
+        color,
+    }
+}
@@ -0,0 +1,1250 @@
+use crate::{
+    camera::Camera,
+    scene::Scene,
+    shader_types::{CameraUniform, RenderUniform, SPHERE_FLAG_SELECTED},
+};
+use encase::{ShaderSize, ShaderType, StorageBuffer, UniformBuffer};
+use wgpu::util::DeviceExt;
+
+/// Per frame state that isn't part of the scene
+#[derive(Clone, Default)]
+pub struct RenderOptions {
+    pub selected_spheres: std::collections::BTreeSet<usize>,
+    pub stereo: bool,
+    /// Render in square tiles of this many pixels with a submit each, so no single submit runs long enough to hit the gpu watchdog
+    pub tile_size: Option<usize>,
+    /// Dither the 8 bit output to hide banding in smooth gradients
+    pub dither: bool,
+    /// Move the scene so the camera sits at the origin before uploading, which keeps precision
+    /// for geometry far from the world origin
+    pub camera_relative: bool,
+    /// Render half the pixels each frame in a checkerboard, keeping the other half from the frame before
+    pub checkerboard: bool,
+    /// Render pixels where the depth jumps between neighbors again with this many extra samples, 0 turns it off
+    pub edge_aa_samples: u32,
+    /// How much the depth has to change relative to itself for a pixel to count as an edge
+    pub edge_aa_threshold: f32,
+    /// Tint pixels whose hit is within this distance of the min or max distance, 0 turns it off
+    pub clip_margin: f32,
+    /// Show luminance as a color ramp from blue for crushed blacks to red for clipped whites
+    pub false_color: bool,
+    /// Show the backplate where rays miss instead of the sky, if one has been set
+    pub backplate: bool,
+    /// Render this many times larger along each axis and average it down, 0 and 1 turn it off
+    pub ssaa: u32,
+    /// World units between the lines of a grid drawn on the y = 0 plane, 0 turns it off
+    pub grid_spacing: f32,
+    pub grid_color: [f32; 3],
+    /// Render everything right of `split` through this camera instead of the scene's
+    pub split_camera: Option<Camera>,
+    /// Where the split camera takes over, from 0 to 1 across the image
+    pub split: f32,
+}
+
+impl From<&RenderOptions> for RenderUniform {
+    fn from(options: &RenderOptions) -> Self {
+        Self {
+            selected_count: options.selected_spheres.len() as _,
+            stereo: options.stereo as _,
+            tile_offset: cgmath::vec2(0, 0),
+            dither: options.dither as _,
+            checkerboard: options.checkerboard as _,
+            checkerboard_phase: 0,
+            edge_aa_threshold: options.edge_aa_threshold,
+            edge_aa_samples: options.edge_aa_samples,
+            clip_margin: options.clip_margin,
+            false_color: options.false_color as _,
+            backplate: options.backplate as _,
+            ssaa: options.ssaa.max(1),
+            grid_spacing: options.grid_spacing,
+            grid_color: options.grid_color.into(),
+            grid_offset: cgmath::vec3(0.0, 0.0, 0.0),
+            split_screen: options.split_camera.is_some() as _,
+            split: options.split,
+        }
+    }
+}
+
+/// The supersampled textures `main` and `edge_aa` render into before `resolve` averages them down
+struct SsaaTargets {
+    factor: u32,
+    texture_bind_group: wgpu::BindGroup,
+    depth_bind_group: wgpu::BindGroup,
+    edge_aa_depth_bind_group: wgpu::BindGroup,
+    resolve_bind_group: wgpu::BindGroup,
+}
+
+pub struct Renderer {
+    texture_size: (usize, usize),
+    texture: wgpu::Texture,
+    hdr_texture: wgpu::Texture,
+    depth_texture: wgpu::Texture,
+    normal_texture: wgpu::Texture,
+    // the hdr texture is only worth reading back once a frame has been rendered into it
+    hdr_texture_rendered: bool,
+    checkerboard_phase: u32,
+    // where the scene's origin got moved to by the last upload, the camera position when rendering camera relative
+    origin: cgmath::Vector3<f32>,
+    texture_bind_group: wgpu::BindGroup,
+    depth_bind_group: wgpu::BindGroup,
+    pipeline: wgpu::ComputePipeline,
+    // shares the main pipeline's bind group layouts apart from the depth texture, which it reads instead
+    edge_aa_depth_bind_group_layout: wgpu::BindGroupLayout,
+    edge_aa_depth_bind_group: wgpu::BindGroup,
+    edge_aa_pipeline: wgpu::ComputePipeline,
+    // reads the supersampled textures and writes the normal sized ones through the main pipeline's layouts
+    resolve_bind_group_layout: wgpu::BindGroupLayout,
+    resolve_pipeline: wgpu::ComputePipeline,
+    // only there while supersampling, and dropped whenever the bind groups they were made for change
+    ssaa_targets: Option<SsaaTargets>,
+    // the wgsl the current pipeline was compiled from
+    shader_source: String,
+    camera_buffer: wgpu::Buffer,
+    render_uniform_buffer: wgpu::Buffer,
+    split_camera_buffer: wgpu::Buffer,
+    camera_bind_group: wgpu::BindGroup,
+    spheres_buffer: wgpu::Buffer,
+    spheres_bind_group: wgpu::BindGroup,
+    spheres_buffer_size: usize,
+    // a 1x1 placeholder until `set_backplate` gets an image, shares the spheres bind group
+    backplate_texture: wgpu::Texture,
+    backplate_sampler: wgpu::Sampler,
+    has_backplate: bool,
+    luminance_bind_group_layout: wgpu::BindGroupLayout,
+    luminance_pipeline: wgpu::ComputePipeline,
+    luminance_buffer: wgpu::Buffer,
+    luminance_readback_buffer: wgpu::Buffer,
+    // set while a measurement is in flight, hears back once the readback buffer is mapped
+    luminance_mapped: Option<std::sync::mpsc::Receiver<Result<(), wgpu::BufferAsyncError>>>,
+}
+
+impl Renderer {
+    pub fn new(device: &wgpu::Device, scene: &Scene) -> Self {
+        let shader_source = include_str!("./shader.wgsl");
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("shader.wgsl"),
+            source: wgpu::ShaderSource::Wgsl(shader_source.into()),
+        });
+
+        let pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: Some("Pipeline"),
+            layout: None,
+            module: &shader,
+            entry_point: "main",
+        });
+
+        let edge_aa_depth_bind_group_layout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("Edge AA depth bind group layout"),
+                entries: &[wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                }],
+            });
+        let edge_aa_pipeline = Self::create_edge_aa_pipeline(
+            device,
+            &shader,
+            &pipeline,
+            &edge_aa_depth_bind_group_layout,
+        );
+
+        let resolve_texture_entry = |binding| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::COMPUTE,
+            ty: wgpu::BindingType::Texture {
+                sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                view_dimension: wgpu::TextureViewDimension::D2,
+                multisampled: false,
+            },
+            count: None,
+        };
+        let resolve_bind_group_layout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("Resolve bind group layout"),
+                entries: &[
+                    resolve_texture_entry(3),
+                    resolve_texture_entry(4),
+                    resolve_texture_entry(5),
+                ],
+            });
+        let resolve_pipeline =
+            Self::create_resolve_pipeline(device, &shader, &pipeline, &resolve_bind_group_layout);
+
+        let (width, height) = (1usize, 1usize);
+        let (texture, hdr_texture, depth_texture, normal_texture, texture_bind_group) =
+            Self::create_textures(device, &pipeline, width, height);
+        let (depth_bind_group, edge_aa_depth_bind_group) = Self::create_depth_bind_groups(
+            device,
+            &pipeline,
+            &edge_aa_depth_bind_group_layout,
+            &depth_texture,
+        );
+
+        let camera_buffer = {
+            let camera_uniform: CameraUniform = scene.camera.into();
+            let mut buffer =
+                UniformBuffer::new([0u8; <CameraUniform as ShaderSize>::SHADER_SIZE.get() as _]);
+            buffer.write(&camera_uniform).unwrap();
+            device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some("Camera Buffer"),
+                contents: &buffer.into_inner(),
+                usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            })
+        };
+        // filled in by every upload, whether or not split screen is on
+        let split_camera_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Split Camera Buffer"),
+            size: <CameraUniform as ShaderSize>::SHADER_SIZE.get(),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let render_uniform_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Render Uniform Buffer"),
+            size: <RenderUniform as ShaderSize>::SHADER_SIZE.get(),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let camera_bind_group = Self::create_camera_bind_group(
+            device,
+            &pipeline,
+            &camera_buffer,
+            &render_uniform_buffer,
+            &split_camera_buffer,
+        );
+
+        let (spheres_buffer, spheres_buffer_size) = {
+            let spheres_storage = scene.spheres_buffer();
+            let mut buffer =
+                StorageBuffer::new(Vec::with_capacity(spheres_storage.size().get() as _));
+            buffer.write(&spheres_storage).unwrap();
+            let buffer = buffer.into_inner();
+            (
+                device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                    label: Some("Sphere Buffer"),
+                    contents: &buffer,
+                    usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+                }),
+                buffer.len(),
+            )
+        };
+
+        let backplate_texture = device.create_texture(&Self::backplate_texture_descriptor((1, 1)));
+        let backplate_sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("Backplate Sampler"),
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+        let spheres_bind_group = Self::create_spheres_bind_group(
+            device,
+            &pipeline,
+            &spheres_buffer,
+            &backplate_texture,
+            &backplate_sampler,
+        );
+
+        let luminance_shader = device.create_shader_module(wgpu::include_wgsl!("./luminance.wgsl"));
+        // spelled out since a derived layout would want a filterable texture, which rgba32float isn't
+        let luminance_bind_group_layout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("Luminance bind group layout"),
+                entries: &[
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 0,
+                        visibility: wgpu::ShaderStages::COMPUTE,
+                        ty: wgpu::BindingType::Texture {
+                            sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                            view_dimension: wgpu::TextureViewDimension::D2,
+                            multisampled: false,
+                        },
+                        count: None,
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 1,
+                        visibility: wgpu::ShaderStages::COMPUTE,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Storage { read_only: false },
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
+                        },
+                        count: None,
+                    },
+                ],
+            });
+        let luminance_pipeline_layout =
+            device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("Luminance Pipeline Layout"),
+                bind_group_layouts: &[&luminance_bind_group_layout],
+                push_constant_ranges: &[],
+            });
+        let luminance_pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: Some("Luminance Pipeline"),
+            layout: Some(&luminance_pipeline_layout),
+            module: &luminance_shader,
+            entry_point: "main",
+        });
+        let luminance_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Luminance Buffer"),
+            size: std::mem::size_of::<f32>() as _,
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_SRC,
+            mapped_at_creation: false,
+        });
+        let luminance_readback_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Luminance Readback Buffer"),
+            size: luminance_buffer.size(),
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+
+        Self {
+            texture_size: (width, height),
+            texture,
+            hdr_texture,
+            depth_texture,
+            normal_texture,
+            hdr_texture_rendered: false,
+            checkerboard_phase: 0,
+            origin: cgmath::vec3(0.0, 0.0, 0.0),
+            texture_bind_group,
+            depth_bind_group,
+            pipeline,
+            edge_aa_depth_bind_group_layout,
+            edge_aa_depth_bind_group,
+            edge_aa_pipeline,
+            resolve_bind_group_layout,
+            resolve_pipeline,
+            ssaa_targets: None,
+            shader_source: shader_source.to_owned(),
+            camera_buffer,
+            render_uniform_buffer,
+            split_camera_buffer,
+            camera_bind_group,
+            spheres_buffer,
+            spheres_bind_group,
+            spheres_buffer_size,
+            backplate_texture,
+            backplate_sampler,
+            has_backplate: false,
+            luminance_bind_group_layout,
+            luminance_pipeline,
+            luminance_buffer,
+            luminance_readback_buffer,
+            luminance_mapped: None,
+        }
+    }
+
+    pub fn shader_source(&self) -> &str {
+        &self.shader_source
+    }
+
+    /// Swaps in a pipeline built from `source`, keeping the current one if it fails to compile.
+    /// The textures get recreated, so anything holding a view of them has to get a new one
+    pub fn reload_shader(&mut self, device: &wgpu::Device, source: &str) -> Result<(), String> {
+        device.push_error_scope(wgpu::ErrorFilter::Validation);
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("shader.wgsl"),
+            source: wgpu::ShaderSource::Wgsl(source.into()),
+        });
+        let pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: Some("Pipeline"),
+            layout: None,
+            module: &shader,
+            entry_point: "main",
+        });
+        let edge_aa_pipeline = Self::create_edge_aa_pipeline(
+            device,
+            &shader,
+            &pipeline,
+            &self.edge_aa_depth_bind_group_layout,
+        );
+        let resolve_pipeline = Self::create_resolve_pipeline(
+            device,
+            &shader,
+            &pipeline,
+            &self.resolve_bind_group_layout,
+        );
+        if let Some(error) = pollster::block_on(device.pop_error_scope()) {
+            return Err(error.to_string());
+        }
+
+        // bind groups are tied to the layouts of the pipeline they were made from
+        let (width, height) = self.texture_size;
+        (
+            self.texture,
+            self.hdr_texture,
+            self.depth_texture,
+            self.normal_texture,
+            self.texture_bind_group,
+        ) = Self::create_textures(device, &pipeline, width, height);
+        (self.depth_bind_group, self.edge_aa_depth_bind_group) = Self::create_depth_bind_groups(
+            device,
+            &pipeline,
+            &self.edge_aa_depth_bind_group_layout,
+            &self.depth_texture,
+        );
+        self.ssaa_targets = None;
+        self.hdr_texture_rendered = false;
+        self.camera_bind_group = Self::create_camera_bind_group(
+            device,
+            &pipeline,
+            &self.camera_buffer,
+            &self.render_uniform_buffer,
+            &self.split_camera_buffer,
+        );
+        self.spheres_bind_group = Self::create_spheres_bind_group(
+            device,
+            &pipeline,
+            &self.spheres_buffer,
+            &self.backplate_texture,
+            &self.backplate_sampler,
+        );
+        self.pipeline = pipeline;
+        self.edge_aa_pipeline = edge_aa_pipeline;
+        self.resolve_pipeline = resolve_pipeline;
+        self.shader_source = source.to_owned();
+        Ok(())
+    }
+
+    fn create_edge_aa_pipeline(
+        device: &wgpu::Device,
+        shader: &wgpu::ShaderModule,
+        pipeline: &wgpu::ComputePipeline,
+        depth_bind_group_layout: &wgpu::BindGroupLayout,
+    ) -> wgpu::ComputePipeline {
+        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Edge AA Pipeline Layout"),
+            bind_group_layouts: &[
+                &pipeline.get_bind_group_layout(0),
+                &pipeline.get_bind_group_layout(1),
+                &pipeline.get_bind_group_layout(2),
+                depth_bind_group_layout,
+            ],
+            push_constant_ranges: &[],
+        });
+        device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: Some("Edge AA Pipeline"),
+            layout: Some(&layout),
+            module: shader,
+            entry_point: "edge_aa",
+        })
+    }
+
+    fn create_resolve_pipeline(
+        device: &wgpu::Device,
+        shader: &wgpu::ShaderModule,
+        pipeline: &wgpu::ComputePipeline,
+        resolve_bind_group_layout: &wgpu::BindGroupLayout,
+    ) -> wgpu::ComputePipeline {
+        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Resolve Pipeline Layout"),
+            bind_group_layouts: &[
+                &pipeline.get_bind_group_layout(0),
+                &pipeline.get_bind_group_layout(1),
+                resolve_bind_group_layout,
+                &pipeline.get_bind_group_layout(3),
+            ],
+            push_constant_ranges: &[],
+        });
+        device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: Some("Resolve Pipeline"),
+            layout: Some(&layout),
+            module: shader,
+            entry_point: "resolve",
+        })
+    }
+
+    /// Makes sure the supersampled textures match `factor` and the current size. The factor gets
+    /// halved until they fit in the device's texture limit
+    fn prepare_ssaa(&mut self, device: &wgpu::Device, factor: u32) {
+        let (width, height) = self.texture_size;
+        let max_size = device.limits().max_texture_dimension_2d as usize;
+        let mut factor = factor.max(1);
+        while factor > 1 && width.max(height) * factor as usize > max_size {
+            factor /= 2;
+        }
+        if factor == 1 {
+            self.ssaa_targets = None;
+            return;
+        }
+        if self
+            .ssaa_targets
+            .as_ref()
+            .is_some_and(|targets| targets.factor == factor)
+        {
+            return;
+        }
+
+        let (_, hdr_texture, depth_texture, normal_texture, texture_bind_group) =
+            Self::create_textures(
+                device,
+                &self.pipeline,
+                width * factor as usize,
+                height * factor as usize,
+            );
+        let (depth_bind_group, edge_aa_depth_bind_group) = Self::create_depth_bind_groups(
+            device,
+            &self.pipeline,
+            &self.edge_aa_depth_bind_group_layout,
+            &depth_texture,
+        );
+        let views = [hdr_texture, normal_texture, depth_texture]
+            .map(|texture| texture.create_view(&wgpu::TextureViewDescriptor::default()));
+        let resolve_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Resolve bind group"),
+            layout: &self.resolve_bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 3,
+                    resource: wgpu::BindingResource::TextureView(&views[0]),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 4,
+                    resource: wgpu::BindingResource::TextureView(&views[1]),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 5,
+                    resource: wgpu::BindingResource::TextureView(&views[2]),
+                },
+            ],
+        });
+        self.ssaa_targets = Some(SsaaTargets {
+            factor,
+            texture_bind_group,
+            depth_bind_group,
+            edge_aa_depth_bind_group,
+            resolve_bind_group,
+        });
+        // the checkerboard has no previous frame at the new size to fill in from
+        self.hdr_texture_rendered = false;
+    }
+
+    /// The depth texture is written by the main pipeline and read by the edge aa one, so each gets its own bind group
+    fn create_depth_bind_groups(
+        device: &wgpu::Device,
+        pipeline: &wgpu::ComputePipeline,
+        edge_aa_depth_bind_group_layout: &wgpu::BindGroupLayout,
+        depth_texture: &wgpu::Texture,
+    ) -> (wgpu::BindGroup, wgpu::BindGroup) {
+        let view = depth_texture.create_view(&wgpu::TextureViewDescriptor::default());
+        (
+            device.create_bind_group(&wgpu::BindGroupDescriptor {
+                label: Some("Depth bind group"),
+                layout: &pipeline.get_bind_group_layout(3),
+                entries: &[wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&view),
+                }],
+            }),
+            device.create_bind_group(&wgpu::BindGroupDescriptor {
+                label: Some("Edge AA depth bind group"),
+                layout: edge_aa_depth_bind_group_layout,
+                entries: &[wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::TextureView(&view),
+                }],
+            }),
+        )
+    }
+
+    fn create_camera_bind_group(
+        device: &wgpu::Device,
+        pipeline: &wgpu::ComputePipeline,
+        camera_buffer: &wgpu::Buffer,
+        render_uniform_buffer: &wgpu::Buffer,
+        split_camera_buffer: &wgpu::Buffer,
+    ) -> wgpu::BindGroup {
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &pipeline.get_bind_group_layout(1),
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: camera_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: render_uniform_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: split_camera_buffer.as_entire_binding(),
+                },
+            ],
+            label: Some("camera_bind_group"),
+        })
+    }
+
+    fn create_spheres_bind_group(
+        device: &wgpu::Device,
+        pipeline: &wgpu::ComputePipeline,
+        spheres_buffer: &wgpu::Buffer,
+        backplate_texture: &wgpu::Texture,
+        backplate_sampler: &wgpu::Sampler,
+    ) -> wgpu::BindGroup {
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &pipeline.get_bind_group_layout(2),
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: spheres_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::TextureView(
+                        &backplate_texture.create_view(&wgpu::TextureViewDescriptor::default()),
+                    ),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: wgpu::BindingResource::Sampler(backplate_sampler),
+                },
+            ],
+            label: Some("spheres_bind_group"),
+        })
+    }
+
+    // srgb, so sampling it gives linear colors like everything else in the shader
+    fn backplate_texture_descriptor(
+        (width, height): (usize, usize),
+    ) -> wgpu::TextureDescriptor<'static> {
+        wgpu::TextureDescriptor {
+            size: wgpu::Extent3d {
+                width: width as _,
+                height: height as _,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::Rgba8UnormSrgb,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+            label: Some("backplate texture"),
+            view_formats: &[],
+        }
+    }
+
+    /// Sets the image shown where rays miss while `RenderOptions::backplate` is on, stretched over
+    /// the whole view. `pixels` are 8 bit srgb rgba rows, `None` goes back to the sky
+    pub fn set_backplate(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        image: Option<((usize, usize), &[u8])>,
+    ) {
+        self.has_backplate = image.is_some();
+        self.backplate_texture = match image {
+            Some((size, pixels)) => device.create_texture_with_data(
+                queue,
+                &Self::backplate_texture_descriptor(size),
+                pixels,
+            ),
+            None => device.create_texture(&Self::backplate_texture_descriptor((1, 1))),
+        };
+        self.spheres_bind_group = Self::create_spheres_bind_group(
+            device,
+            &self.pipeline,
+            &self.spheres_buffer,
+            &self.backplate_texture,
+            &self.backplate_sampler,
+        );
+    }
+
+    pub fn has_backplate(&self) -> bool {
+        self.has_backplate
+    }
+
+    fn create_textures(
+        device: &wgpu::Device,
+        pipeline: &wgpu::ComputePipeline,
+        width: usize,
+        height: usize,
+    ) -> (
+        wgpu::Texture,
+        wgpu::Texture,
+        wgpu::Texture,
+        wgpu::Texture,
+        wgpu::BindGroup,
+    ) {
+        let create_texture = |format, usage, label| {
+            device.create_texture(&wgpu::TextureDescriptor {
+                size: wgpu::Extent3d {
+                    width: width as _,
+                    height: height as _,
+                    depth_or_array_layers: 1,
+                },
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format,
+                usage,
+                label: Some(label),
+                view_formats: &[],
+            })
+        };
+        let texture = create_texture(
+            wgpu::TextureFormat::Rgba8Unorm,
+            wgpu::TextureUsages::TEXTURE_BINDING
+                | wgpu::TextureUsages::STORAGE_BINDING
+                | wgpu::TextureUsages::COPY_SRC,
+            "texture",
+        );
+        // the same image before it gets clamped to 0..1, only used for exporting
+        let hdr_texture = create_texture(
+            wgpu::TextureFormat::Rgba32Float,
+            wgpu::TextureUsages::TEXTURE_BINDING
+                | wgpu::TextureUsages::STORAGE_BINDING
+                | wgpu::TextureUsages::COPY_SRC,
+            "hdr texture",
+        );
+        let depth_texture = create_texture(
+            wgpu::TextureFormat::R32Float,
+            wgpu::TextureUsages::TEXTURE_BINDING
+                | wgpu::TextureUsages::STORAGE_BINDING
+                | wgpu::TextureUsages::COPY_SRC,
+            "depth texture",
+        );
+        // world space normals of the first hit, for compositing
+        let normal_texture = create_texture(
+            wgpu::TextureFormat::Rgba32Float,
+            wgpu::TextureUsages::TEXTURE_BINDING
+                | wgpu::TextureUsages::STORAGE_BINDING
+                | wgpu::TextureUsages::COPY_SRC,
+            "normal texture",
+        );
+
+        let texture_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Texture bind group"),
+            layout: &pipeline.get_bind_group_layout(0),
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(
+                        &texture.create_view(&wgpu::TextureViewDescriptor::default()),
+                    ),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::TextureView(
+                        &hdr_texture.create_view(&wgpu::TextureViewDescriptor::default()),
+                    ),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: wgpu::BindingResource::TextureView(
+                        &normal_texture.create_view(&wgpu::TextureViewDescriptor::default()),
+                    ),
+                },
+            ],
+        });
+
+        (
+            texture,
+            hdr_texture,
+            depth_texture,
+            normal_texture,
+            texture_bind_group,
+        )
+    }
+
+    pub fn texture(&self) -> &wgpu::Texture {
+        &self.texture
+    }
+
+    pub fn texture_size(&self) -> (usize, usize) {
+        self.texture_size
+    }
+
+    pub fn hdr_texture_rendered(&self) -> bool {
+        self.hdr_texture_rendered
+    }
+
+    /// Recreates the output texture if the size changed, returns whether it did
+    pub fn resize(
+        &mut self,
+        device: &wgpu::Device,
+        size @ (width, height): (usize, usize),
+    ) -> bool {
+        if self.texture_size == size || width == 0 || height == 0 {
+            return false;
+        }
+        (
+            self.texture,
+            self.hdr_texture,
+            self.depth_texture,
+            self.normal_texture,
+            self.texture_bind_group,
+        ) = Self::create_textures(device, &self.pipeline, width, height);
+        (self.depth_bind_group, self.edge_aa_depth_bind_group) = Self::create_depth_bind_groups(
+            device,
+            &self.pipeline,
+            &self.edge_aa_depth_bind_group_layout,
+            &self.depth_texture,
+        );
+        self.texture_size = size;
+        self.ssaa_targets = None;
+        self.hdr_texture_rendered = false;
+        true
+    }
+
+    pub fn render(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        scene: &Scene,
+        options: RenderOptions,
+    ) -> wgpu::SubmissionIndex {
+        self.upload_scene(device, queue, scene, &options);
+        self.prepare_ssaa(device, options.ssaa);
+        let submission_index = self.dispatch(device, queue, &options, &self.texture_bind_group);
+        self.hdr_texture_rendered = true;
+        submission_index
+    }
+
+    /// Renders into a texture owned by the caller instead of the internal one, `view` has to be
+    /// an `Rgba8Unorm` texture with `STORAGE_BINDING` usage and `size` is its size in pixels.
+    /// The internal textures get resized to match, since the hdr and normal outputs still go there
+    pub fn render_to_view(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        scene: &Scene,
+        options: RenderOptions,
+        view: &wgpu::TextureView,
+        size: (usize, usize),
+    ) -> wgpu::SubmissionIndex {
+        self.resize(device, size);
+        let texture_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Target texture bind group"),
+            layout: &self.pipeline.get_bind_group_layout(0),
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::TextureView(
+                        &self
+                            .hdr_texture
+                            .create_view(&wgpu::TextureViewDescriptor::default()),
+                    ),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: wgpu::BindingResource::TextureView(
+                        &self
+                            .normal_texture
+                            .create_view(&wgpu::TextureViewDescriptor::default()),
+                    ),
+                },
+            ],
+        });
+        self.upload_scene(device, queue, scene, &options);
+        self.prepare_ssaa(device, options.ssaa);
+        let submission_index = self.dispatch(device, queue, &options, &texture_bind_group);
+        self.hdr_texture_rendered = true;
+        submission_index
+    }
+
+    fn upload_scene(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        scene: &Scene,
+        options: &RenderOptions,
+    ) {
+        if options.checkerboard {
+            self.checkerboard_phase ^= 1;
+        }
+
+        let origin = if options.camera_relative {
+            scene.camera.position
+        } else {
+            cgmath::vec3(0.0, 0.0, 0.0)
+        };
+        self.origin = origin;
+
+        // Update camera uniforms
+        for (camera, camera_buffer) in [
+            (scene.camera, &self.camera_buffer),
+            (
+                options.split_camera.unwrap_or(scene.camera),
+                &self.split_camera_buffer,
+            ),
+        ] {
+            let mut camera = camera;
+            camera.position -= origin;
+            let camera_uniform: CameraUniform = camera.into();
+            let mut buffer =
+                UniformBuffer::new([0u8; <CameraUniform as ShaderSize>::SHADER_SIZE.get() as _]);
+            buffer.write(&camera_uniform).unwrap();
+            queue.write_buffer(camera_buffer, 0, &buffer.into_inner());
+        }
+
+        // Update spheres buffer
+        {
+            let mut spheres_storage = scene.spheres_buffer();
+            for (i, sphere) in spheres_storage.spheres.iter_mut().enumerate() {
+                sphere.position -= origin;
+                sphere.flags &= !SPHERE_FLAG_SELECTED;
+                if options.selected_spheres.contains(&i) {
+                    sphere.flags |= SPHERE_FLAG_SELECTED;
+                }
+            }
+            let mut buffer =
+                StorageBuffer::new(Vec::with_capacity(spheres_storage.size().get() as _));
+            buffer.write(&spheres_storage).unwrap();
+            let buffer = buffer.into_inner();
+            if self.spheres_buffer_size < buffer.len() {
+                self.spheres_buffer =
+                    device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                        label: Some("Sphere Buffer"),
+                        contents: &buffer,
+                        usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+                    });
+
+                self.spheres_bind_group = Self::create_spheres_bind_group(
+                    device,
+                    &self.pipeline,
+                    &self.spheres_buffer,
+                    &self.backplate_texture,
+                    &self.backplate_sampler,
+                );
+
+                self.spheres_buffer_size = buffer.len();
+            } else {
+                queue.write_buffer(&self.spheres_buffer, 0, &buffer);
+            }
+        }
+    }
+
+    fn dispatch(
+        &self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        options: &RenderOptions,
+        texture_bind_group: &wgpu::BindGroup,
+    ) -> wgpu::SubmissionIndex {
+        const WORKGROUP_SIZE: usize = 16;
+
+        let mut render_uniform: RenderUniform = options.into();
+        // freshly created textures have no previous frame to fill in the other half
+        render_uniform.checkerboard = (options.checkerboard && self.hdr_texture_rendered) as _;
+        render_uniform.checkerboard_phase = self.checkerboard_phase;
+        render_uniform.backplate = (options.backplate && self.has_backplate) as _;
+        if options.grid_spacing > 0.0 {
+            // wrapping keeps the lines precise however far the camera relative origin is from the world one
+            let spacing = options.grid_spacing;
+            render_uniform.grid_offset = cgmath::vec3(
+                self.origin.x.rem_euclid(spacing),
+                self.origin.y,
+                self.origin.z.rem_euclid(spacing),
+            );
+        }
+
+        let (width, height) = self.texture_size;
+        // `main` and `edge_aa` render into whichever textures `resolve` reads from, if there is a resolve
+        let (render_size, render_bind_groups) = match &self.ssaa_targets {
+            Some(targets) => {
+                render_uniform.ssaa = targets.factor;
+                let factor = targets.factor as usize;
+                (
+                    (width * factor, height * factor),
+                    (
+                        &targets.texture_bind_group,
+                        &targets.depth_bind_group,
+                        &targets.edge_aa_depth_bind_group,
+                    ),
+                )
+            }
+            None => (
+                (width, height),
+                (
+                    texture_bind_group,
+                    &self.depth_bind_group,
+                    &self.edge_aa_depth_bind_group,
+                ),
+            ),
+        };
+        let (render_texture_bind_group, depth_bind_group, edge_aa_depth_bind_group) =
+            render_bind_groups;
+        let mut passes = vec![(
+            &self.pipeline,
+            render_texture_bind_group,
+            &self.spheres_bind_group,
+            depth_bind_group,
+            render_size,
+        )];
+        // every tile of the main pass has to be done first, edges need the depth of their neighbors
+        if options.edge_aa_samples > 0 {
+            passes.push((
+                &self.edge_aa_pipeline,
+                render_texture_bind_group,
+                &self.spheres_bind_group,
+                edge_aa_depth_bind_group,
+                render_size,
+            ));
+        }
+        if let Some(targets) = &self.ssaa_targets {
+            passes.push((
+                &self.resolve_pipeline,
+                texture_bind_group,
+                &targets.resolve_bind_group,
+                &self.depth_bind_group,
+                (width, height),
+            ));
+        }
+
+        let mut submission_index = None;
+        for (pipeline, texture_bind_group, spheres_bind_group, depth_bind_group, (width, height)) in
+            passes
+        {
+            // tiles are whole workgroups so none of them overlap
+            let (tile_width, tile_height) = match options.tile_size {
+                Some(tile_size) => {
+                    let tile_size = tile_size.max(1).next_multiple_of(WORKGROUP_SIZE);
+                    (tile_size, tile_size)
+                }
+                None => (width, height),
+            };
+            for tile_y in (0..height).step_by(tile_height) {
+                for tile_x in (0..width).step_by(tile_width) {
+                    // Update render uniform, the write lands before this tile's submit
+                    {
+                        render_uniform.tile_offset = cgmath::vec2(tile_x as _, tile_y as _);
+                        let mut buffer = UniformBuffer::new(
+                            [0u8; <RenderUniform as ShaderSize>::SHADER_SIZE.get() as _],
+                        );
+                        buffer.write(&render_uniform).unwrap();
+                        queue.write_buffer(&self.render_uniform_buffer, 0, &buffer.into_inner());
+                    }
+
+                    let mut encoder = device
+                        .create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+                    {
+                        let (dispatch_with, dispatch_height) = (
+                            tile_width.min(width - tile_x).div_ceil(WORKGROUP_SIZE),
+                            tile_height.min(height - tile_y).div_ceil(WORKGROUP_SIZE),
+                        );
+                        let mut compute_pass =
+                            encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+                                label: Some("Compute pass"),
+                            });
+                        compute_pass.set_pipeline(pipeline);
+                        compute_pass.set_bind_group(0, texture_bind_group, &[]);
+                        compute_pass.set_bind_group(1, &self.camera_bind_group, &[]);
+                        compute_pass.set_bind_group(2, spheres_bind_group, &[]);
+                        compute_pass.set_bind_group(3, depth_bind_group, &[]);
+                        compute_pass.dispatch_workgroups(
+                            dispatch_with as _,
+                            dispatch_height as _,
+                            1,
+                        );
+                    }
+                    submission_index = Some(queue.submit([encoder.finish()]));
+                }
+            }
+        }
+        // the texture is never empty so there is always at least one tile
+        submission_index.unwrap()
+    }
+
+    /// The geometric mean luminance of a recent hdr frame, or `None` while the gpu is still working
+    /// on it. This never waits, a new measurement of the last frame starts once the previous one
+    /// has been read
+    pub fn average_luminance(&mut self, device: &wgpu::Device, queue: &wgpu::Queue) -> Option<f32> {
+        if let Some(mapped) = &self.luminance_mapped {
+            device.poll(wgpu::Maintain::Poll);
+            return match mapped.try_recv() {
+                Err(std::sync::mpsc::TryRecvError::Empty) => None,
+                Ok(Ok(())) => {
+                    let slice = self.luminance_readback_buffer.slice(..);
+                    let luminance =
+                        f32::from_le_bytes(slice.get_mapped_range()[..4].try_into().unwrap());
+                    self.luminance_readback_buffer.unmap();
+                    self.luminance_mapped = None;
+                    Some(luminance)
+                }
+                // the next call starts over with a new measurement
+                Ok(Err(_)) | Err(std::sync::mpsc::TryRecvError::Disconnected) => {
+                    self.luminance_mapped = None;
+                    None
+                }
+            };
+        }
+
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Luminance bind group"),
+            layout: &self.luminance_bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(
+                        &self
+                            .hdr_texture
+                            .create_view(&wgpu::TextureViewDescriptor::default()),
+                    ),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: self.luminance_buffer.as_entire_binding(),
+                },
+            ],
+        });
+
+        let mut encoder =
+            device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+        {
+            let mut compute_pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+                label: Some("Luminance pass"),
+            });
+            compute_pass.set_pipeline(&self.luminance_pipeline);
+            compute_pass.set_bind_group(0, &bind_group, &[]);
+            compute_pass.dispatch_workgroups(1, 1, 1);
+        }
+        encoder.copy_buffer_to_buffer(
+            &self.luminance_buffer,
+            0,
+            &self.luminance_readback_buffer,
+            0,
+            self.luminance_buffer.size(),
+        );
+        queue.submit([encoder.finish()]);
+
+        let (sender, mapped) = std::sync::mpsc::channel();
+        self.luminance_readback_buffer
+            .slice(..)
+            .map_async(wgpu::MapMode::Read, move |result| {
+                _ = sender.send(result);
+            });
+        self.luminance_mapped = Some(mapped);
+        None
+    }
+
+    /// Copies the output texture into a new texture that later renders leave alone
+    pub fn snapshot_texture(&self, device: &wgpu::Device, queue: &wgpu::Queue) -> wgpu::Texture {
+        let (width, height) = self.texture_size;
+        let size = wgpu::Extent3d {
+            width: width as _,
+            height: height as _,
+            depth_or_array_layers: 1,
+        };
+        let snapshot = device.create_texture(&wgpu::TextureDescriptor {
+            size,
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::Rgba8Unorm,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+            label: Some("snapshot texture"),
+            view_formats: &[],
+        });
+        let mut encoder =
+            device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+        encoder.copy_texture_to_texture(
+            self.texture.as_image_copy(),
+            snapshot.as_image_copy(),
+            size,
+        );
+        queue.submit([encoder.finish()]);
+        snapshot
+    }
+
+    /// Copies the output texture back to the cpu as tightly packed rgba8 rows
+    pub fn read_texture(&self, device: &wgpu::Device, queue: &wgpu::Queue) -> Vec<u8> {
+        Self::read_rows(device, queue, &self.texture, self.texture_size, 4)
+    }
+
+    /// Copies the unclamped output back to the cpu as tightly packed linear rgba rows
+    pub fn read_hdr_texture(&self, device: &wgpu::Device, queue: &wgpu::Queue) -> Vec<[f32; 4]> {
+        Self::read_float_rows(device, queue, &self.hdr_texture, self.texture_size)
+    }
+
+    /// Copies the world space normal of what each pixel's ray hit first back to the cpu,
+    /// alpha is 1 for hits and the sky is all zeros
+    pub fn read_normal_texture(&self, device: &wgpu::Device, queue: &wgpu::Queue) -> Vec<[f32; 4]> {
+        Self::read_float_rows(device, queue, &self.normal_texture, self.texture_size)
+    }
+
+    fn read_float_rows(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        texture: &wgpu::Texture,
+        size: (usize, usize),
+    ) -> Vec<[f32; 4]> {
+        Self::read_rows(device, queue, texture, size, 16)
+            .chunks_exact(16)
+            .map(|pixel| {
+                std::array::from_fn(|i| {
+                    f32::from_le_bytes(pixel[i * 4..i * 4 + 4].try_into().unwrap())
+                })
+            })
+            .collect()
+    }
+
+    /// Copies the distance along each pixel's ray to what it hit back to the cpu, misses are `max_distance`
+    pub fn read_depth_texture(&self, device: &wgpu::Device, queue: &wgpu::Queue) -> Vec<f32> {
+        Self::read_rows(device, queue, &self.depth_texture, self.texture_size, 4)
+            .chunks_exact(4)
+            .map(|depth| f32::from_le_bytes(depth.try_into().unwrap()))
+            .collect()
+    }
+
+    fn read_rows(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        texture: &wgpu::Texture,
+        (width, height): (usize, usize),
+        bytes_per_pixel: usize,
+    ) -> Vec<u8> {
+        // rows in a texture to buffer copy have to be padded to a multiple of 256 bytes
+        let padded_bytes_per_row = (width * bytes_per_pixel)
+            .div_ceil(wgpu::COPY_BYTES_PER_ROW_ALIGNMENT as usize)
+            * wgpu::COPY_BYTES_PER_ROW_ALIGNMENT as usize;
+
+        let readback_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Readback Buffer"),
+            size: (padded_bytes_per_row * height) as _,
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+
+        let mut encoder =
+            device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+        encoder.copy_texture_to_buffer(
+            texture.as_image_copy(),
+            wgpu::ImageCopyBuffer {
+                buffer: &readback_buffer,
+                layout: wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: std::num::NonZeroU32::new(padded_bytes_per_row as _),
+                    rows_per_image: None,
+                },
+            },
+            wgpu::Extent3d {
+                width: width as _,
+                height: height as _,
+                depth_or_array_layers: 1,
+            },
+        );
+        let submission_index = queue.submit([encoder.finish()]);
+
+        let slice = readback_buffer.slice(..);
+        slice.map_async(wgpu::MapMode::Read, |_| {});
+        device.poll(wgpu::Maintain::WaitForSubmissionIndex(submission_index));
+
+        let mut pixels = Vec::with_capacity(width * height * bytes_per_pixel);
+        for row in slice.get_mapped_range().chunks(padded_bytes_per_row) {
+            pixels.extend_from_slice(&row[..width * bytes_per_pixel]);
+        }
+        readback_buffer.unmap();
+        pixels
+    }
+}
@@ -0,0 +1,161 @@
+use crate::{
+    camera::Camera,
+    shader_types::{Sphere, SpheresBuffer},
+};
+use encase::ArrayLength;
+
+#[derive(Clone, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct Scene {
+    /// The active camera, its entry in `cameras` is only updated when switching away or saving
+    pub camera: Camera,
+    pub cameras: Vec<NamedCamera>,
+    pub active_camera: usize,
+    pub spheres: Vec<Sphere>,
+    /// Lines up with `spheres` but can be shorter, a missing or empty name means the sphere is unnamed
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub sphere_names: Vec<String>,
+    /// Lines up with `spheres` after `sync_sphere_ids`, so the editor can keep its state on the same
+    /// sphere when they get reordered or deleted. Not saved, loading hands out new ones
+    #[serde(skip)]
+    pub sphere_ids: Vec<u64>,
+}
+
+#[derive(Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct NamedCamera {
+    pub name: String,
+    pub camera: Camera,
+}
+
+pub fn clamp_albedo(color: [f32; 3]) -> [f32; 3] {
+    color.map(|channel| channel.clamp(0.0, 1.0))
+}
+
+// far brighter than any display or exr viewer cares about, with headroom left for the exposure
+const MAX_SKY_RADIANCE: f32 = 1e6;
+
+/// Negative light makes no sense, this also turns NaNs into 0 and caps infinities
+pub fn clamp_sky_color(color: [f32; 3]) -> [f32; 3] {
+    color.map(|channel| {
+        if channel.is_nan() {
+            0.0
+        } else {
+            channel.clamp(0.0, MAX_SKY_RADIANCE)
+        }
+    })
+}
+
+impl Default for Scene {
+    fn default() -> Self {
+        Self {
+            camera: Camera::default(),
+            cameras: vec![NamedCamera {
+                name: "Camera".to_string(),
+                camera: Camera::default(),
+            }],
+            active_camera: 0,
+            spheres: vec![Sphere::default()],
+            sphere_names: vec![],
+            sphere_ids: vec![],
+        }
+    }
+}
+
+impl Scene {
+    pub fn load(path: &std::path::Path) -> Result<Self, String> {
+        let text = std::fs::read_to_string(path).map_err(|error| error.to_string())?;
+        let mut scene: Self = serde_json::from_str(&text).map_err(|error| error.to_string())?;
+        for sphere in &mut scene.spheres {
+            sphere.color = clamp_albedo(sphere.color.into()).into();
+            sphere.opacity = sphere.opacity.clamp(0.0, 1.0);
+        }
+        let cameras = std::iter::once(&mut scene.camera)
+            .chain(scene.cameras.iter_mut().map(|named| &mut named.camera));
+        for camera in cameras {
+            camera.up_sky_color = clamp_sky_color(camera.up_sky_color.into()).into();
+            camera.down_sky_color = clamp_sky_color(camera.down_sky_color.into()).into();
+            camera.sky_intensity = camera.sky_intensity.max(0.0);
+        }
+        // a hand edited camera list can leave the active camera without an entry
+        if scene.active_camera >= scene.cameras.len() {
+            scene.cameras.push(NamedCamera {
+                name: "Camera".to_string(),
+                camera: scene.camera,
+            });
+            scene.active_camera = scene.cameras.len() - 1;
+        }
+        Ok(scene)
+    }
+
+    pub fn save(&self, path: &std::path::Path) -> Result<(), String> {
+        let mut scene = self.clone();
+        scene.store_camera();
+        let named_count = scene
+            .sphere_names
+            .iter()
+            .rposition(|name| !name.is_empty())
+            .map_or(0, |index| index + 1);
+        scene.sphere_names.truncate(named_count);
+        let text = serde_json::to_string_pretty(&scene).map_err(|error| error.to_string())?;
+        std::fs::write(path, text).map_err(|error| error.to_string())
+    }
+
+    /// The name to show for the sphere at `index`, falling back to its index
+    pub fn sphere_label(&self, index: usize) -> String {
+        match self.sphere_names.get(index) {
+            Some(name) if !name.is_empty() => name.clone(),
+            _ => format!("Sphere {index}"),
+        }
+    }
+
+    /// Gives any spheres without an id a new one, and drops the ids past the end of `spheres`
+    pub fn sync_sphere_ids(&mut self) {
+        static NEXT_ID: std::sync::atomic::AtomicU64 = std::sync::atomic::AtomicU64::new(0);
+        self.sphere_ids.resize_with(self.spheres.len(), || {
+            NEXT_ID.fetch_add(1, std::sync::atomic::Ordering::Relaxed)
+        });
+    }
+
+    /// Writes the active camera back into its entry in `cameras`
+    pub fn store_camera(&mut self) {
+        if let Some(named) = self.cameras.get_mut(self.active_camera) {
+            named.camera = self.camera;
+        }
+    }
+
+    /// Makes `index` the active camera, keeping the changes made to the previous one
+    pub fn switch_camera(&mut self, index: usize) {
+        self.store_camera();
+        if let Some(named) = self.cameras.get(index) {
+            self.camera = named.camera;
+            self.active_camera = index;
+        }
+    }
+
+    /// The axis aligned bounding box over all spheres, or `None` if there are none
+    pub fn bounds(&self) -> Option<(cgmath::Vector3<f32>, cgmath::Vector3<f32>)> {
+        self.spheres.iter().map(|sphere| sphere.bounds()).reduce(
+            |(min_a, max_a), (min_b, max_b)| {
+                (
+                    cgmath::vec3(
+                        min_a.x.min(min_b.x),
+                        min_a.y.min(min_b.y),
+                        min_a.z.min(min_b.z),
+                    ),
+                    cgmath::vec3(
+                        max_a.x.max(max_b.x),
+                        max_a.y.max(max_b.y),
+                        max_a.z.max(max_b.z),
+                    ),
+                )
+            },
+        )
+    }
+
+    pub fn spheres_buffer(&self) -> SpheresBuffer {
+        SpheresBuffer {
+            sphere_count: ArrayLength,
+            spheres: self.spheres.clone(),
+        }
+    }
+}
@@ -12,12 +12,55 @@ struct Camera {
     down_sky_color: vec3<f32>,
     min_distance: f32,
     max_distance: f32,
+    shadow_bias: f32,
+    eye_separation: f32,
+    vignette_radius: f32,
+    vignette_intensity: f32,
+    fog_color: vec3<f32>,
+    fog_density: f32,
+    fog_sky: u32,
+    exposure: f32,
+    pixel_aspect: f32,
+}
+
+struct RenderUniform {
+    selected_count: u32,
+    stereo: u32,
+    tile_offset: vec2<u32>,
+    dither: u32,
+    checkerboard: u32,
+    checkerboard_phase: u32,
+    edge_aa_threshold: f32,
+    edge_aa_samples: u32,
+    clip_margin: f32,
+    false_color: u32,
+    backplate: u32,
+    ssaa: u32,
+    grid_spacing: f32,
+    grid_color: vec3<f32>,
+    grid_offset: vec3<f32>,
+    split_screen: u32,
+    split: f32,
 }
 
 struct Sphere {
     position: vec3<f32>,
     radius: f32,
     color: vec3<f32>,
+    flags: u32,
+    emission: vec3<f32>,
+    emission_strength: f32,
+    opacity: f32,
+}
+
+const SPHERE_FLAG_SMOOTH_NORMALS: u32 = 1u;
+const SPHERE_FLAG_SELECTED: u32 = 2u;
+const SPHERE_FLAG_CSG_SUBTRACT: u32 = 4u;
+const SPHERE_FLAG_CSG_INTERSECT: u32 = 8u;
+const SPHERE_FLAG_TWO_SIDED: u32 = 16u;
+
+fn is_selected(sphere: Sphere) -> bool {
+    return (sphere.flags & SPHERE_FLAG_SELECTED) != 0u;
 }
 
 struct SpheresBuffer {
@@ -29,40 +72,121 @@ struct SpheresBuffer {
 @binding(0)
 var output_texture: texture_storage_2d<rgba8unorm, write>;
 
+@group(0)
+@binding(1)
+var hdr_output_texture: texture_storage_2d<rgba32float, write>;
+
+// the world space normal of the first hit with an alpha of 1, all zeros for the sky
+@group(0)
+@binding(2)
+var normal_output_texture: texture_storage_2d<rgba32float, write>;
+
+// how far along the ray the first hit was, `max_distance` for the sky
+@group(3)
+@binding(0)
+var depth_output_texture: texture_storage_2d<r32float, write>;
+
+// the same texture, `edge_aa` reads it after `main` has filled it in
+@group(3)
+@binding(1)
+var depth_texture: texture_2d<f32>;
+
 @group(1)
 @binding(0)
-var<uniform> camera: Camera;
+var<uniform> main_camera: Camera;
+
+@group(1)
+@binding(1)
+var<uniform> render: RenderUniform;
+
+// used right of `render.split` while `render.split_screen` is set
+@group(1)
+@binding(2)
+var<uniform> split_camera: Camera;
+
+// whichever camera the current pixel is seen through, set by `select_camera`
+var<private> camera: Camera;
+
+// every entry point has to call this before anything reads `camera`
+fn select_camera(coords: vec2<i32>, size: vec2<i32>) {
+    if render.split_screen != 0u && f32(coords.x) + 0.5 >= render.split * f32(size.x) {
+        camera = split_camera;
+    } else {
+        camera = main_camera;
+    }
+}
 
 @group(2)
 @binding(0)
 var<storage> spheres_storage: SpheresBuffer;
 
+// shown where rays miss while `render.backplate` is set
+@group(2)
+@binding(1)
+var backplate_texture: texture_2d<f32>;
+
+@group(2)
+@binding(2)
+var backplate_sampler: sampler;
+
+// what `main` rendered at `render.ssaa` times the size, `resolve` averages each block of it into one pixel
+@group(2)
+@binding(3)
+var ssaa_hdr_texture: texture_2d<f32>;
+
+@group(2)
+@binding(4)
+var ssaa_normal_texture: texture_2d<f32>;
+
+@group(2)
+@binding(5)
+var ssaa_depth_texture: texture_2d<f32>;
+
 fn sphere_sdf(position: vec3<f32>, sphere: Sphere) -> f32 {
     return distance(position, sphere.position) - sphere.radius;
 }
 
+// how many spheres the shape starting at `index` uses, a csg sphere takes the next sphere with it
+fn shape_size(index: u32) -> u32 {
+    let csg_flags = SPHERE_FLAG_CSG_SUBTRACT | SPHERE_FLAG_CSG_INTERSECT;
+    if (spheres_storage.spheres[index].flags & csg_flags) != 0u && index + 1u < spheres_storage.sphere_count {
+        return 2u;
+    }
+    return 1u;
+}
+
+fn shape_sdf(position: vec3<f32>, index: u32) -> f32 {
+    let sphere = spheres_storage.spheres[index];
+    let dist = sphere_sdf(position, sphere);
+    if shape_size(index) == 1u {
+        return dist;
+    }
+
+    let other_dist = sphere_sdf(position, spheres_storage.spheres[index + 1u]);
+    if (sphere.flags & SPHERE_FLAG_CSG_SUBTRACT) != 0u {
+        return max(dist, -other_dist);
+    }
+    return max(dist, other_dist);
+}
+
 fn sdf(position: vec3<f32>) -> f32 {
     if spheres_storage.sphere_count == 0u {
         return 0.0;
     }
 
-    var closest_sphere = 0u;
-    var dist = sphere_sdf(position, spheres_storage.spheres[0]);
-    for (var i: u32 = 1u; i < spheres_storage.sphere_count; i++) {
-        let new_dist = sphere_sdf(position, spheres_storage.spheres[i]);
-        if new_dist < dist {
-            closest_sphere = i;
-            dist = new_dist;
-        }
+    var dist = shape_sdf(position, 0u);
+    for (var i: u32 = shape_size(0u); i < spheres_storage.sphere_count; i += shape_size(i)) {
+        dist = min(dist, shape_sdf(position, i));
     }
     return dist;
 }
 
-fn get_normal(p: vec3<f32>) -> vec3<f32> {
+// only looks at the shape that was hit, so a see-through shape around it doesn't bend the normal
+fn get_normal(p: vec3<f32>, index: u32) -> vec3<f32> {
     return normalize(vec3<f32>(
-        sdf(vec3<f32>(p.x + camera.min_distance, p.y, p.z)) - sdf(vec3<f32>(p.x - camera.min_distance, p.y, p.z)),
-        sdf(vec3<f32>(p.x, p.y + camera.min_distance, p.z)) - sdf(vec3<f32>(p.x, p.y - camera.min_distance, p.z)),
-        sdf(vec3<f32>(p.x, p.y, p.z + camera.min_distance)) - sdf(vec3<f32>(p.x, p.y, p.z - camera.min_distance))
+        shape_sdf(vec3<f32>(p.x + camera.min_distance, p.y, p.z), index) - shape_sdf(vec3<f32>(p.x - camera.min_distance, p.y, p.z), index),
+        shape_sdf(vec3<f32>(p.x, p.y + camera.min_distance, p.z), index) - shape_sdf(vec3<f32>(p.x, p.y - camera.min_distance, p.z), index),
+        shape_sdf(vec3<f32>(p.x, p.y, p.z + camera.min_distance), index) - shape_sdf(vec3<f32>(p.x, p.y, p.z - camera.min_distance), index)
     ));
 }
 
@@ -81,16 +205,65 @@ fn does_hit(ray: Ray) -> bool {
     return false;
 }
 
-fn get_color(ray: Ray) -> vec3<f32> {
+const OUTLINE_COLOR: vec3<f32> = vec3<f32>(1.0, 0.6, 0.0);
+
+// exponential fog, `distance` is how far the ray travelled from the camera
+fn apply_fog(color: vec3<f32>, distance: f32) -> vec3<f32> {
+    return mix(camera.fog_color, color, exp(-camera.fog_density * distance));
+}
+
+// where the ray being traced is in its view, 0 to 1 from the top left, set by `shade_pixel`
+var<private> screen_uv: vec2<f32>;
+
+// set by `get_color`
+var<private> hit_distance: f32;
+var<private> hit_normal: vec4<f32>;
+// the last step of the march, small when a miss ran out of distance right next to a surface
+var<private> last_step: f32;
+
+// how many see-through shapes a ray can pass through, anything after that is treated as opaque
+const MAX_LAYERS: u32 = 4u;
+
+// `outline_width` is the angular width of the selection outline, in the same units as the ray direction
+fn get_color(ray: Ray, outline_width: f32) -> vec3<f32> {
     var ray = ray;
+    hit_distance = camera.max_distance;
+    hit_normal = vec4<f32>(0.0);
+    last_step = camera.max_distance;
+
+    // see-through shapes that were hit get blended in and then skipped for the rest of the ray
+    var color = vec3<f32>(0.0);
+    var transmittance = 1.0;
+    var layers: array<u32, MAX_LAYERS>;
+    var layer_count = 0u;
+
+    // the smallest angle between the ray and the surface of any selected sphere
+    var selected_angle = outline_width;
 
     if spheres_storage.sphere_count != 0u {
         var distance: f32 = 0.0;
         while distance < camera.max_distance {
+            if render.selected_count != 0u {
+                var selected_dist = camera.max_distance;
+                for (var i: u32 = 0u; i < spheres_storage.sphere_count; i++) {
+                    if is_selected(spheres_storage.spheres[i]) {
+                        selected_dist = min(selected_dist, sphere_sdf(ray.origin, spheres_storage.spheres[i]));
+                    }
+                }
+                selected_angle = min(selected_angle, selected_dist / max(distance, camera.min_distance));
+            }
+
             var closest_sphere = 0u;
-            var dist = sphere_sdf(ray.origin, spheres_storage.spheres[0]);
-            for (var i: u32 = 1u; i < spheres_storage.sphere_count; i++) {
-                let new_dist = sphere_sdf(ray.origin, spheres_storage.spheres[i]);
+            var dist = camera.max_distance;
+            for (var i: u32 = 0u; i < spheres_storage.sphere_count; i += shape_size(i)) {
+                var skipped = false;
+                for (var layer = 0u; layer < layer_count; layer++) {
+                    skipped = skipped || layers[layer] == i;
+                }
+                if skipped {
+                    continue;
+                }
+                let new_dist = shape_sdf(ray.origin, i);
                 if new_dist < dist {
                     closest_sphere = i;
                     dist = new_dist;
@@ -98,24 +271,202 @@ fn get_color(ray: Ray) -> vec3<f32> {
             }
             ray.origin += ray.direction * dist;
             distance += dist;
+            last_step = dist;
             if dist < camera.min_distance {
+                if layer_count == 0u {
+                    hit_distance = distance;
+                }
+                if !is_selected(spheres_storage.spheres[closest_sphere]) && selected_angle < outline_width {
+                    return OUTLINE_COLOR;
+                }
+
                 let light_direction = normalize(vec3<f32>(0.3, -1.0, 0.4));
 
-                let normal = get_normal(ray.origin);
+                let sphere = spheres_storage.spheres[closest_sphere];
+                var normal: vec3<f32>;
+                // the analytic normal is only right for a plain sphere
+                if (sphere.flags & SPHERE_FLAG_SMOOTH_NORMALS) != 0u && shape_size(closest_sphere) == 1u {
+                    normal = normalize(ray.origin - sphere.position);
+                } else {
+                    normal = get_normal(ray.origin, closest_sphere);
+                }
+                if (sphere.flags & SPHERE_FLAG_TWO_SIDED) != 0u && dot(normal, ray.direction) > 0.0 {
+                    normal = -normal;
+                }
+                if layer_count == 0u {
+                    hit_normal = vec4<f32>(normal, 1.0);
+                }
 
                 var new_ray: Ray;
-                new_ray.origin = ray.origin + normal * camera.min_distance * 2.0;
+                new_ray.origin = ray.origin + normal * camera.shadow_bias;
                 new_ray.direction = -light_direction;
                 let does_hit = does_hit(new_ray);
 
                 let light_amount = max(f32(!does_hit) * dot(normal, -light_direction), 0.05);
-                return spheres_storage.spheres[closest_sphere].color * light_amount;
+                let surface_color = apply_fog(sphere.color * light_amount + sphere.emission * sphere.emission_strength, distance);
+                let opacity = clamp(sphere.opacity, 0.0, 1.0);
+                if opacity >= 1.0 || layer_count == MAX_LAYERS {
+                    return color + transmittance * surface_color;
+                }
+                color += transmittance * opacity * surface_color;
+                transmittance *= 1.0 - opacity;
+                layers[layer_count] = closest_sphere;
+                layer_count += 1u;
             }
         }
     }
 
+    if selected_angle < outline_width {
+        return OUTLINE_COLOR;
+    }
+
+    // a photo to composite over, so it is mapped to the screen and never fogged
+    if render.backplate != 0u {
+        return color + transmittance * textureSampleLevel(backplate_texture, backplate_sampler, screen_uv, 0.0).rgb;
+    }
+
     let t = ray.direction.y * 0.5 + 0.5;
-    return camera.up_sky_color * (1.0 - t) + camera.down_sky_color * t;
+    var sky_color = camera.up_sky_color * (1.0 - t) + camera.down_sky_color * t;
+    if camera.fog_sky != 0u {
+        sky_color = apply_fog(sky_color, camera.max_distance);
+    }
+    return color + transmittance * sky_color;
+}
+
+// a 4x4 bayer matrix offset to be centered on 0, scaled to about one 8 bit step
+fn dither_offset(coords: vec2<i32>) -> f32 {
+    var bayer = array<f32, 16>(
+        0.0, 8.0, 2.0, 10.0,
+        12.0, 4.0, 14.0, 6.0,
+        3.0, 11.0, 1.0, 9.0,
+        15.0, 7.0, 13.0, 5.0,
+    );
+    let index = (coords.y & 3) * 4 + (coords.x & 3);
+    return ((bayer[index] + 0.5) / 16.0 - 0.5) / 255.0;
+}
+
+// blends the ground grid over `color` where the world y = 0 plane is in front of the first hit,
+// `pixel_angle` is how far apart neighboring rays are in the same units as the ray direction
+fn apply_grid(color: vec3<f32>, ray: Ray, pixel_angle: f32) -> vec3<f32> {
+    let t = -(ray.origin.y + render.grid_offset.y) / ray.direction.y;
+    if !(t > 0.0) || t >= hit_distance {
+        return color;
+    }
+
+    let position = (ray.origin.xz + render.grid_offset.xz + ray.direction.xz * t) / render.grid_spacing;
+    // how much of the plane a pixel covers in grid cells, it gets stretched out at grazing angles
+    let footprint = t * pixel_angle / max(abs(ray.direction.y), 0.001) / render.grid_spacing;
+    let line_distance = abs(fract(position + 0.5) - 0.5);
+    let lines = 1.0 - smoothstep(vec2<f32>(0.0), vec2<f32>(footprint), line_distance);
+    // lines closer together than a few pixels would just shimmer, so they fade out first
+    let fade = 1.0 - smoothstep(0.1, 0.5, footprint);
+    return mix(color, render.grid_color, max(lines.x, lines.y) * fade);
+}
+
+const NEAR_CLIP_COLOR: vec3<f32> = vec3<f32>(0.0, 0.5, 1.0);
+const FAR_CLIP_COLOR: vec3<f32> = vec3<f32>(1.0, 0.0, 1.0);
+
+// the color of the pixel at `coords` with the ray moved `offset` pixels, before dithering
+fn shade_pixel(coords: vec2<i32>, offset: vec2<f32>, size: vec2<i32>) -> vec3<f32> {
+    var pixel = vec2<f32>(coords);
+    var view_size = vec2<f32>(size);
+    var origin = camera.position;
+    if render.stereo != 0u {
+        // left eye in the left half, right eye in the right half
+        view_size.x *= 0.5;
+        var eye = -0.5;
+        if pixel.x >= view_size.x {
+            pixel.x -= view_size.x;
+            eye = 0.5;
+        }
+        origin += camera.right * eye * camera.eye_separation;
+    }
+    pixel += offset;
+    screen_uv = pixel / view_size;
+
+    var uv = pixel / view_size;
+    uv.y = 1.0 - uv.y;
+    uv = uv * 2.0 - 1.0;
+
+    let aspect = view_size.x / view_size.y * camera.pixel_aspect;
+
+    var ray: Ray;
+    ray.origin = origin;
+    ray.direction = normalize(camera.right * uv.x * aspect + camera.up * uv.y + camera.forward);
+
+    // two pixels of the final image wide, even when supersampling
+    let outline_width = 4.0 * f32(render.ssaa) / f32(size.y);
+    var color = get_color(ray, outline_width) * camera.exposure;
+
+    if render.grid_spacing > 0.0 {
+        // a pixel of the final image, even when supersampling
+        color = apply_grid(color, ray, 2.0 * f32(render.ssaa) / f32(size.y));
+    }
+
+    if render.clip_margin > 0.0 {
+        // geometry right at either end of the march range is probably being cut off
+        let hit = hit_distance < camera.max_distance;
+        if hit && hit_distance < camera.min_distance + render.clip_margin {
+            color = mix(color, NEAR_CLIP_COLOR, 0.5);
+        } else if select(last_step < render.clip_margin, hit_distance > camera.max_distance - render.clip_margin, hit) {
+            color = mix(color, FAR_CLIP_COLOR, 0.5);
+        }
+    }
+
+    if camera.vignette_intensity > 0.0 {
+        // 0 in the middle of each view and 1 in its corners
+        let center_distance = length(uv) / sqrt(2.0);
+        color *= 1.0 - camera.vignette_intensity * smoothstep(camera.vignette_radius, 1.0, center_distance);
+    }
+    return color;
+}
+
+// exposure analysis colors, keyed by stops from middle grey
+fn false_color(color: vec3<f32>) -> vec3<f32> {
+    let luminance = dot(color, vec3<f32>(0.2126, 0.7152, 0.0722));
+    if luminance >= 1.0 {
+        return vec3<f32>(1.0, 0.0, 0.0);
+    }
+    let stops = log2(max(luminance, 0.000001) / 0.18);
+
+    // blue for crushed blacks, green around middle grey, orange just before clipping
+    var keys = array<f32, 5>(-6.0, -3.0, 0.0, 1.5, 2.5);
+    var colors = array<vec3<f32>, 5>(
+        vec3<f32>(0.0, 0.0, 0.6),
+        vec3<f32>(0.0, 0.6, 1.0),
+        vec3<f32>(0.2, 0.8, 0.2),
+        vec3<f32>(1.0, 1.0, 0.0),
+        vec3<f32>(1.0, 0.5, 0.0),
+    );
+    if stops <= keys[0] {
+        return colors[0];
+    }
+    for (var i = 1; i < 5; i++) {
+        if stops < keys[i] {
+            return mix(colors[i - 1], colors[i], (stops - keys[i - 1]) / (keys[i] - keys[i - 1]));
+        }
+    }
+    return colors[4];
+}
+
+fn store_color(coords: vec2<i32>, color: vec3<f32>) {
+    var output_color = color;
+    if render.false_color != 0u {
+        output_color = false_color(color);
+    } else if render.dither != 0u {
+        output_color += vec3<f32>(dither_offset(coords));
+    }
+    textureStore(output_texture, coords.xy, vec4<f32>(output_color, 1.0));
+    textureStore(hdr_output_texture, coords.xy, vec4<f32>(color, 1.0));
+}
+
+// whether `coords` should be rendered this frame
+fn in_frame(coords: vec2<i32>, size: vec2<i32>) -> bool {
+    if coords.x >= size.x || coords.y >= size.y {
+        return false;
+    }
+    // the skipped pixels still hold the other half from the previous frame
+    return render.checkerboard == 0u || u32(coords.x + coords.y) % 2u == render.checkerboard_phase;
 }
 
 @compute
@@ -124,22 +475,91 @@ fn main(
     @builtin(global_invocation_id) global_id: vec3<u32>
 ) {
     let size = textureDimensions(output_texture);
-    let coords = vec2<i32>(global_id.xy);
+    let coords = vec2<i32>(global_id.xy + render.tile_offset);
+    if !in_frame(coords, size) {
+        return;
+    }
+    select_camera(coords, size);
 
-    if coords.x >= size.x || coords.y >= size.y {
+    store_color(coords, shade_pixel(coords, vec2<f32>(0.0), size));
+    textureStore(depth_output_texture, coords.xy, vec4<f32>(hit_distance, 0.0, 0.0, 0.0));
+    textureStore(normal_output_texture, coords.xy, hit_normal);
+}
+
+// runs after `main`, and renders the pixels where the depth jumps again with extra samples
+@compute
+@workgroup_size(16, 16)
+fn edge_aa(
+    @builtin(global_invocation_id) global_id: vec3<u32>
+) {
+    let size = textureDimensions(output_texture);
+    let coords = vec2<i32>(global_id.xy + render.tile_offset);
+    if !in_frame(coords, size) {
         return;
     }
+    select_camera(coords, size);
 
-    var uv = vec2<f32>(coords) / vec2<f32>(size);
-    uv.y = 1.0 - uv.y;
-    uv = uv * 2.0 - 1.0;
+    let depth = textureLoad(depth_texture, coords, 0).r;
+    var directions = array<vec2<i32>, 4>(
+        vec2<i32>(1, 0),
+        vec2<i32>(-1, 0),
+        vec2<i32>(0, 1),
+        vec2<i32>(0, -1),
+    );
+    var is_edge = false;
+    for (var i = 0; i < 4; i++) {
+        let neighbor = clamp(coords + directions[i], vec2<i32>(0), size - 1);
+        let neighbor_depth = textureLoad(depth_texture, neighbor, 0).r;
+        // relative, so far away edges aren't all counted
+        is_edge = is_edge || abs(neighbor_depth - depth) > render.edge_aa_threshold * min(neighbor_depth, depth);
+    }
+    if !is_edge {
+        return;
+    }
 
-    let aspect = f32(size.x) / f32(size.y);
+    var color = shade_pixel(coords, vec2<f32>(0.0), size);
+    for (var i = 1u; i <= render.edge_aa_samples; i++) {
+        // an r2 sequence spreads any number of samples evenly over the pixel
+        let offset = fract(f32(i) * vec2<f32>(0.7548776662, 0.5698402909)) - 0.5;
+        color += shade_pixel(coords, offset, size);
+    }
+    store_color(coords, color / f32(render.edge_aa_samples + 1u));
+}
 
-    var ray: Ray;
-    ray.origin = camera.position;
-    ray.direction = normalize(camera.right * uv.x * aspect + camera.up * uv.y + camera.forward);
+// runs after `main` and `edge_aa` have rendered the supersampled textures, and box filters them down
+@compute
+@workgroup_size(16, 16)
+fn resolve(
+    @builtin(global_invocation_id) global_id: vec3<u32>
+) {
+    let size = textureDimensions(output_texture);
+    let coords = vec2<i32>(global_id.xy + render.tile_offset);
+    if coords.x >= size.x || coords.y >= size.y {
+        return;
+    }
+    select_camera(coords, size);
 
-    let color = get_color(ray);
-    textureStore(output_texture, coords.xy, vec4<f32>(color, 1.0));
+    let factor = i32(render.ssaa);
+    var color = vec3<f32>(0.0);
+    var normal = vec4<f32>(0.0);
+    var depth = camera.max_distance;
+    for (var y = 0; y < factor; y++) {
+        for (var x = 0; x < factor; x++) {
+            let sample_coords = coords * factor + vec2<i32>(x, y);
+            color += textureLoad(ssaa_hdr_texture, sample_coords, 0).rgb;
+            normal += textureLoad(ssaa_normal_texture, sample_coords, 0);
+            depth = min(depth, textureLoad(ssaa_depth_texture, sample_coords, 0).r);
+        }
+    }
+    let sample_count = f32(factor * factor);
+
+    // averaged while still linear, before `store_color` clamps it into the 8 bit output
+    store_color(coords, color / sample_count);
+    textureStore(depth_output_texture, coords.xy, vec4<f32>(depth, 0.0, 0.0, 0.0));
+    // the alpha ends up as how much of the pixel was covered by a hit
+    var direction = normal.xyz;
+    if length(direction) > 0.0 {
+        direction = normalize(direction);
+    }
+    textureStore(normal_output_texture, coords.xy, vec4<f32>(direction, normal.w / sample_count));
 }
@@ -0,0 +1,111 @@
+use encase::{ArrayLength, ShaderType};
+
+#[derive(Clone, Copy, ShaderType)]
+pub struct CameraUniform {
+    pub position: cgmath::Vector3<f32>,
+    pub forward: cgmath::Vector3<f32>,
+    pub right: cgmath::Vector3<f32>,
+    pub up: cgmath::Vector3<f32>,
+    pub up_sky_color: cgmath::Vector3<f32>,
+    pub down_sky_color: cgmath::Vector3<f32>,
+    pub min_distance: f32,
+    pub max_distance: f32,
+    pub shadow_bias: f32,
+    pub eye_separation: f32,
+    pub vignette_radius: f32,
+    // 0 when the vignette is disabled
+    pub vignette_intensity: f32,
+    pub fog_color: cgmath::Vector3<f32>,
+    pub fog_density: f32,
+    // non zero to also fog the sky, as if it was at `max_distance`
+    pub fog_sky: u32,
+    pub exposure: f32,
+    // scales the horizontal spread of the rays on top of the aspect ratio
+    pub pixel_aspect: f32,
+}
+
+#[derive(Clone, Copy, ShaderType)]
+pub struct RenderUniform {
+    // which spheres are selected is stored in their flags, this just lets the shader skip outlines
+    pub selected_count: u32,
+    // non zero to render the left and right eye views side by side
+    pub stereo: u32,
+    // added to the invocation id, so a dispatch can cover just part of the image
+    pub tile_offset: cgmath::Vector2<u32>,
+    // non zero to dither the rgba8 output, the hdr output is never dithered
+    pub dither: u32,
+    // non zero to only render every other pixel, the rest keep what the previous frame wrote
+    pub checkerboard: u32,
+    // which half of the checkerboard gets rendered, alternates every frame
+    pub checkerboard_phase: u32,
+    // how much the depth has to change between neighbors, relative to the depth, to count as an edge
+    pub edge_aa_threshold: f32,
+    // extra samples for edge pixels, the edge pass is skipped entirely when this is 0
+    pub edge_aa_samples: u32,
+    // hits this close to the min or max distance get tinted, 0 turns the overlay off
+    pub clip_margin: f32,
+    // non zero to replace the rgba8 output with a luminance ramp, the hdr output is left alone
+    pub false_color: u32,
+    // non zero to show the backplate texture where rays miss instead of the sky
+    pub backplate: u32,
+    // how many times larger along each axis `main` rendered, which `resolve` averages back down
+    pub ssaa: u32,
+    // world units between the lines of the ground grid, 0 turns the grid off
+    pub grid_spacing: f32,
+    pub grid_color: cgmath::Vector3<f32>,
+    // where the shader's origin is in world space, wrapped to the grid spacing along x and z
+    pub grid_offset: cgmath::Vector3<f32>,
+    // non zero to render everything right of `split` with the second camera uniform
+    pub split_screen: u32,
+    // from 0 to 1 across the image
+    pub split: f32,
+}
+
+#[derive(Clone, Copy, PartialEq, ShaderType, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct Sphere {
+    pub position: cgmath::Vector3<f32>,
+    pub radius: f32,
+    // kept within 0..1, anything brighter would reflect more light than it receives
+    pub color: cgmath::Vector3<f32>,
+    // a combination of the `SPHERE_FLAG_*` bits, fits in the padding after `color`
+    pub flags: u32,
+    pub emission: cgmath::Vector3<f32>,
+    // multiplies `emission`, this is the only way to get a color above 1
+    pub emission_strength: f32,
+    // 1 is solid, anything less blends the sphere over what is behind it for primary rays
+    pub opacity: f32,
+}
+
+/// Use the analytic surface normal instead of the sdf gradient, exact for spheres
+pub const SPHERE_FLAG_SMOOTH_NORMALS: u32 = 1 << 0;
+/// Only set on the copy of the spheres the renderer uploads, for outlining the selection
+pub const SPHERE_FLAG_SELECTED: u32 = 1 << 1;
+/// Carve the next sphere in the list out of this one, the next sphere isn't drawn by itself
+pub const SPHERE_FLAG_CSG_SUBTRACT: u32 = 1 << 2;
+/// Only draw where this and the next sphere in the list overlap, the next sphere isn't drawn by itself
+pub const SPHERE_FLAG_CSG_INTERSECT: u32 = 1 << 3;
+/// Shade the inside like the outside by flipping the normal towards the ray, otherwise the
+/// inside only gets ambient light
+pub const SPHERE_FLAG_TWO_SIDED: u32 = 1 << 4;
+
+impl Default for Sphere {
+    fn default() -> Self {
+        Self {
+            position: (0.0, 0.0, 0.0).into(),
+            radius: 1.0,
+            color: (1.0, 1.0, 1.0).into(),
+            flags: 0,
+            emission: (0.0, 0.0, 0.0).into(),
+            emission_strength: 1.0,
+            opacity: 1.0,
+        }
+    }
+}
+
+#[derive(Clone, ShaderType)]
+pub struct SpheresBuffer {
+    pub sphere_count: ArrayLength,
+    #[size(runtime)]
+    pub spheres: Vec<Sphere>,
+}
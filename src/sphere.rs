@@ -0,0 +1,116 @@
+use crate::shader_types::{
+    Sphere, SPHERE_FLAG_CSG_INTERSECT, SPHERE_FLAG_CSG_SUBTRACT, SPHERE_FLAG_SMOOTH_NORMALS,
+    SPHERE_FLAG_TWO_SIDED,
+};
+use cgmath::InnerSpace;
+use eframe::egui;
+
+impl Sphere {
+    /// The axis aligned bounding box, as `(min, max)`
+    pub fn bounds(&self) -> (cgmath::Vector3<f32>, cgmath::Vector3<f32>) {
+        let extent = cgmath::vec3(self.radius, self.radius, self.radius);
+        (self.position - extent, self.position + extent)
+    }
+
+    /// The distance along the ray to the closest hit in front of `origin`, `direction` must be normalized.
+    /// This ignores csg, so picking a carved out part still hits the sphere
+    pub fn intersect(
+        &self,
+        origin: cgmath::Vector3<f32>,
+        direction: cgmath::Vector3<f32>,
+    ) -> Option<f32> {
+        let offset = origin - self.position;
+        let b = offset.dot(direction);
+        let c = offset.magnitude2() - self.radius * self.radius;
+        let discriminant = b * b - c;
+        if discriminant < 0.0 {
+            return None;
+        }
+        let root = discriminant.sqrt();
+        [-b - root, -b + root]
+            .into_iter()
+            .find(|&distance| distance >= 0.0)
+    }
+
+    /// Shows the editing widgets, positions get snapped to `grid_size` when it is set
+    pub fn edit_ui(&mut self, ui: &mut egui::Ui, id: egui::Id, grid_size: Option<f32>) {
+        ui.horizontal(|ui| {
+            ui.label("Position:");
+            for (value, prefix) in [
+                (&mut self.position.x, "x: "),
+                (&mut self.position.y, "y: "),
+                (&mut self.position.z, "z: "),
+            ] {
+                let response = ui.add(egui::DragValue::new(value).prefix(prefix).speed(0.1));
+                // snapping only once the edit is done keeps dragging smooth
+                if let Some(grid_size) = grid_size {
+                    if response.drag_released() || response.lost_focus() {
+                        *value = (*value / grid_size).round() * grid_size;
+                    }
+                }
+            }
+        });
+        ui.horizontal(|ui| {
+            ui.label("Radius:");
+            ui.add(egui::DragValue::new(&mut self.radius).speed(0.1));
+        });
+        ui.horizontal(|ui| {
+            ui.label("Albedo:");
+            let mut color = self.color.into();
+            crate::albedo_edit(ui, id.with("Albedo"), &mut color);
+            self.color = color.into();
+        });
+        ui.horizontal(|ui| {
+            ui.label("Emission:");
+            let mut emission = self.emission.into();
+            crate::color_edit(ui, id.with("Emission"), &mut emission);
+            self.emission = emission.into();
+            ui.add(
+                egui::DragValue::new(&mut self.emission_strength)
+                    .prefix("intensity: ")
+                    .speed(0.1),
+            );
+            self.emission_strength = self.emission_strength.max(0.0);
+        });
+        ui.horizontal(|ui| {
+            ui.label("Opacity:");
+            ui.add(egui::Slider::new(&mut self.opacity, 0.0..=1.0))
+                .on_hover_text("Blend the sphere over whatever is behind it, shadows stay solid");
+        });
+        let mut smooth_normals = self.flags & SPHERE_FLAG_SMOOTH_NORMALS != 0;
+        if ui.checkbox(&mut smooth_normals, "Smooth Normals").changed() {
+            self.flags ^= SPHERE_FLAG_SMOOTH_NORMALS;
+        }
+        let mut two_sided = self.flags & SPHERE_FLAG_TWO_SIDED != 0;
+        if ui
+            .checkbox(&mut two_sided, "Two Sided")
+            .on_hover_text("Shade the inside of the sphere like the outside")
+            .changed()
+        {
+            self.flags ^= SPHERE_FLAG_TWO_SIDED;
+        }
+        ui.horizontal(|ui| {
+            const CSG_OPERATIONS: [(&str, u32); 3] = [
+                ("Union", 0),
+                ("Subtract", SPHERE_FLAG_CSG_SUBTRACT),
+                ("Intersect", SPHERE_FLAG_CSG_INTERSECT),
+            ];
+            let csg_flags = SPHERE_FLAG_CSG_SUBTRACT | SPHERE_FLAG_CSG_INTERSECT;
+            let mut operation = self.flags & csg_flags;
+            ui.label("CSG With Next:");
+            egui::ComboBox::from_id_source(id.with("CSG"))
+                .selected_text(
+                    CSG_OPERATIONS
+                        .iter()
+                        .find(|&&(_, flag)| flag == operation)
+                        .map_or("Union", |&(name, _)| name),
+                )
+                .show_ui(ui, |ui| {
+                    for (name, flag) in CSG_OPERATIONS {
+                        ui.selectable_value(&mut operation, flag, name);
+                    }
+                });
+            self.flags = (self.flags & !csg_flags) | operation;
+        });
+    }
+}
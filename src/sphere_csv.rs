@@ -0,0 +1,77 @@
+use crate::shader_types::Sphere;
+
+/// Parses `x,y,z,radius,r,g,b` rows, blank lines and a leading header row are skipped
+pub fn parse_spheres_csv(text: &str) -> Result<Vec<Sphere>, String> {
+    let mut spheres = vec![];
+    for (line_index, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || (line_index == 0 && line.starts_with('x')) {
+            continue;
+        }
+        let values = line
+            .split(',')
+            .map(|value| {
+                let value = value.trim();
+                value
+                    .parse::<f32>()
+                    .map_err(|_| format!("line {}: '{value}' is not a number", line_index + 1))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let &[x, y, z, radius, r, g, b] = values.as_slice() else {
+            return Err(format!(
+                "line {}: expected 7 values but got {}",
+                line_index + 1,
+                values.len()
+            ));
+        };
+        spheres.push(Sphere {
+            position: (x, y, z).into(),
+            radius,
+            color: crate::scene::clamp_albedo([r, g, b]).into(),
+            ..Default::default()
+        });
+    }
+    Ok(spheres)
+}
+
+pub fn load_spheres_csv(path: &std::path::Path) -> Result<Vec<Sphere>, String> {
+    let text = std::fs::read_to_string(path).map_err(|error| error.to_string())?;
+    parse_spheres_csv(&text)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn header_and_blank_lines_are_skipped() {
+        let spheres =
+            parse_spheres_csv("x,y,z,radius,r,g,b\n1, 2, 3, 0.5, 1, 0, 0\n\n-1,0,0,2,0,2,-1\n")
+                .unwrap();
+        assert_eq!(spheres.len(), 2);
+        assert_eq!(spheres[0].position, cgmath::vec3(1.0, 2.0, 3.0));
+        assert_eq!(spheres[0].radius, 0.5);
+        assert_eq!(spheres[0].color, cgmath::vec3(1.0, 0.0, 0.0));
+        // albedo gets clamped like everywhere else
+        assert_eq!(spheres[1].color, cgmath::vec3(0.0, 1.0, 0.0));
+    }
+
+    #[test]
+    fn rejects_bad_rows() {
+        for (text, error) in [
+            ("0,0,0,1,1,1,one", "line 1: 'one' is not a number"),
+            (
+                "0,0,0,1,1,1,1\n0,0,0,1,1,1",
+                "line 2: expected 7 values but got 6",
+            ),
+            ("0,0,0,1,1,1,1,1", "line 1: expected 7 values but got 8"),
+            // only the first line can be a header
+            (
+                "0,0,0,1,1,1,1\nx,y,z,radius,r,g,b",
+                "line 2: 'x' is not a number",
+            ),
+        ] {
+            assert_eq!(parse_spheres_csv(text).err().as_deref(), Some(error));
+        }
+    }
+}
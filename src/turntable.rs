@@ -0,0 +1,93 @@
+use crate::{
+    camera::Orbit,
+    export,
+    renderer::{RenderOptions, Renderer},
+    scene::Scene,
+};
+use cgmath::{InnerSpace, Rotation3};
+
+/// Renders a full orbit around the middle of the scene into numbered pngs, one frame at a time
+/// so the ui can show progress in between
+pub struct Turntable {
+    renderer: Renderer,
+    scene: Scene,
+    orbit: Orbit,
+    options: RenderOptions,
+    output_prefix: std::path::PathBuf,
+    frame: usize,
+    frame_count: usize,
+}
+
+impl Turntable {
+    /// Frames get saved as `<output_prefix>_0000.png` and so on
+    pub fn new(
+        device: &wgpu::Device,
+        scene: &Scene,
+        output_prefix: &std::path::Path,
+        size: (usize, usize),
+        frame_count: usize,
+        options: RenderOptions,
+    ) -> Self {
+        let center = scene
+            .bounds()
+            .map_or(cgmath::vec3(0.0, 0.0, 0.0), |(min, max)| (min + max) * 0.5);
+        let radius = (scene.camera.position - center).magnitude().max(0.1);
+        // keeps the current viewing angle, just pointed at the middle
+        let mut orbit = Orbit::from_camera(&scene.camera, radius);
+        orbit.target = center;
+
+        let mut renderer = Renderer::new(device, scene);
+        renderer.resize(device, size);
+        Self {
+            renderer,
+            scene: scene.clone(),
+            orbit,
+            options,
+            output_prefix: output_prefix.to_owned(),
+            frame: 0,
+            frame_count: frame_count.max(1),
+        }
+    }
+
+    /// How far along the turntable is, from 0 to 1
+    pub fn progress(&self) -> f32 {
+        self.frame as f32 / self.frame_count as f32
+    }
+
+    /// Renders and saves the next frame, returns whether there are any frames left
+    pub fn render_next(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        color_space: export::PngColorSpace,
+    ) -> Result<bool, String> {
+        let mut orbit = self.orbit;
+        // around the world up axis, so a tilted start stays tilted the same way
+        orbit.rotation = cgmath::Quaternion::from_angle_y(cgmath::Deg(
+            360.0 * self.frame as f32 / self.frame_count as f32,
+        )) * orbit.rotation;
+        orbit.apply(&mut self.scene.camera);
+
+        let submission_index =
+            self.renderer
+                .render(device, queue, &self.scene, self.options.clone());
+        device.poll(wgpu::Maintain::WaitForSubmissionIndex(submission_index));
+
+        let pixels = self.renderer.read_hdr_texture(device, queue);
+        let mut file_name = self
+            .output_prefix
+            .file_name()
+            .unwrap_or_default()
+            .to_owned();
+        file_name.push(format!("_{:04}.png", self.frame));
+        export::save_png(
+            &self.output_prefix.with_file_name(file_name),
+            self.renderer.texture_size(),
+            &pixels,
+            color_space,
+        )?;
+
+        self.frame += 1;
+        Ok(self.frame < self.frame_count)
+    }
+}